@@ -0,0 +1,31 @@
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future equivalent of
+/// [std::panic::catch_unwind](https://doc.rust-lang.org/std/panic/fn.catch_unwind.html). Resolves
+/// to `Err` with the panic payload if polling the inner future panics, so generated code can catch
+/// panics in async tests across `.await` points.
+pub struct CatchUnwind<F> {
+    future: F,
+}
+
+impl<F: Future + Unpin> CatchUnwind<F> {
+    pub fn new(future: F) -> Self {
+        CatchUnwind { future }
+    }
+}
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = &mut self.future;
+        match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(future).poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+use crate::test_info::current_test;
+
+pub(crate) type Cleanup = Box<dyn FnOnce()>;
+
+thread_local! {
+    /// The cleanups of the test running on this thread, or `None` outside of a test.
+    static CLEANUPS: RefCell<Option<Vec<Cleanup>>> = const { RefCell::new(None) };
+}
+
+/// Registers `f` to run at the end of the current test, whether it passes or panics. Cleanups run
+/// in the reverse order of their registration, before the `after_each` hook of the test, so setup
+/// in `before_each` or in the test itself can undo what it did so far even if it panics halfway.
+///
+/// A panicking cleanup fails the test, unless the test had already failed. Then the panic is only
+/// reported, so it doesn't hide the original failure. The remaining cleanups run either way.
+///
+/// Panics if it isn't called from a test with any of the before/after hooks or tags applied.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){
+///         println!("creating the schema");
+///         cleanup(|| println!("I get run at the end of every test"));
+///     }
+///     #[test]
+///     fn test_1(){
+///         cleanup(|| println!("I get run first, since I was registered last"));
+///     }
+/// }
+/// ```
+pub fn cleanup(f: impl FnOnce() + 'static) {
+    CLEANUPS.with(|cleanups| match &mut *cleanups.borrow_mut() {
+        Some(cleanups) => cleanups.push(Box::new(f)),
+        None => panic!(
+            "`cleanup` can only be called from a test with any of the before/after hooks or tags applied"
+        ),
+    });
+}
+
+/// Starts collecting the cleanups of a test, returning those of the test it was nested in, if any.
+pub(crate) fn enter() -> Option<Vec<Cleanup>> {
+    CLEANUPS.with(|cleanups| cleanups.replace(Some(vec![])))
+}
+
+/// Runs the remaining cleanups of the test and goes back to collecting those of `previous`. Returns
+/// the panic of a cleanup that should fail the test, like [`run_cleanups`].
+pub(crate) fn exit(previous: Option<Vec<Cleanup>>) -> Option<Box<dyn Any + Send>> {
+    let err = run_cleanups(thread::panicking());
+    CLEANUPS.with(|cleanups| *cleanups.borrow_mut() = previous);
+    err
+}
+
+/// Runs the cleanups registered so far, last one first. Returns the panic of the first one that
+/// panicked, unless the test already `failed`, in which case the panic is only reported.
+pub fn run_cleanups(failed: bool) -> Option<Box<dyn Any + Send>> {
+    let mut first_panic = None;
+    while let Some(cleanup) = CLEANUPS.with(|cleanups| cleanups.borrow_mut().as_mut()?.pop()) {
+        if let Err(err) = panic::catch_unwind(AssertUnwindSafe(cleanup)) {
+            if failed {
+                let name = current_test().map(|test| test.full_name());
+                eprintln!(
+                    "a cleanup of `{}` panicked after the test had already failed",
+                    name.as_deref().unwrap_or("the test")
+                );
+            } else {
+                first_panic.get_or_insert(err);
+            }
+        }
+    }
+    first_panic
+}
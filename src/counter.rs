@@ -0,0 +1,160 @@
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Once, OnceLock};
+use std::time::Instant;
+
+use crate::events::{self, Event};
+use crate::summary::ModuleSummary;
+use crate::tags::tags_selected;
+
+/// Counts down the tests of a mod with `after_all` applied, keeping track of how they went for the
+/// [`ModuleSummary`]. Tests that `TEST_TAGS` skips are never run, and neither are `#[ignore]`d ones
+/// unless the test harness is told to, so the count is worked out from the tags of every test and
+/// whether it is ignored the first time it is needed.
+pub struct TestCounter {
+    module_path: &'static str,
+    tests: &'static [(usize, &'static [&'static str], bool)],
+    state: OnceLock<State>,
+    passed: AtomicUsize,
+    failed: AtomicUsize,
+    done: Once,
+}
+
+struct State {
+    remaining: AtomicUsize,
+    skipped: usize,
+    started: Instant,
+}
+
+impl TestCounter {
+    /// `module_path` is the path of the mod the tests belong to, and `tests` holds the number of
+    /// tests generated by each test function along with its tags and whether it is `#[ignore]`d.
+    /// Tests the mod attributes can't see, like ones generated by macros, add themselves with
+    /// [`RegisteredTest`].
+    pub const fn new(
+        module_path: &'static str,
+        tests: &'static [(usize, &'static [&'static str], bool)],
+    ) -> Self {
+        TestCounter {
+            module_path,
+            tests,
+            state: OnceLock::new(),
+            passed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            done: Once::new(),
+        }
+    }
+
+    fn state(&self) -> &State {
+        self.state.get_or_init(|| {
+            let registered = inventory::iter::<RegisteredTest>
+                .into_iter()
+                .filter(|test| ptr::eq(test.counter, self))
+                .map(|test| (test.count, test.tags, test.ignored));
+            let (selected, skipped) = self.tests.iter().copied().chain(registered).fold(
+                (0, 0),
+                |(selected, skipped), (count, tags, ignored)| {
+                    if tags_selected(tags) && harness_runs(ignored) {
+                        (selected + count, skipped)
+                    } else {
+                        (selected, skipped + count)
+                    }
+                },
+            );
+            State {
+                remaining: AtomicUsize::new(selected),
+                skipped,
+                started: Instant::now(),
+            }
+        })
+    }
+
+    /// Marks a test as started, which starts the clock of the [`ModuleSummary`] for the first one.
+    pub fn start(&self) {
+        self.state();
+    }
+
+    /// Marks one test as finished if it won't be rerun. If it was the last one, runs `after_all`
+    /// with the summary of the mod and reports that the mod is complete.
+    pub fn finish(&self, final_run: bool, passed: bool, after_all: impl FnOnce(&ModuleSummary)) {
+        if !final_run || !self.finish_one(passed) {
+            return;
+        }
+        self.done.call_once(|| {
+            let summary = self.summary();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| after_all(&summary)));
+            events::emit(Event::ModuleFinished {
+                module_path: self.module_path,
+                summary: &summary,
+            });
+            if let Err(err) = result {
+                panic::resume_unwind(err);
+            }
+        });
+    }
+
+    /// Marks one test as finished, returning `true` if it was the last one.
+    fn finish_one(&self, passed: bool) -> bool {
+        if passed {
+            self.passed.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.failed.fetch_add(1, Ordering::SeqCst);
+        }
+        self.state().remaining.fetch_sub(1, Ordering::SeqCst) == 1
+    }
+
+    /// How the tests that have finished so far went.
+    fn summary(&self) -> ModuleSummary {
+        let state = self.state();
+        ModuleSummary::new(
+            self.passed.load(Ordering::SeqCst),
+            self.failed.load(Ordering::SeqCst),
+            state.skipped,
+            state.started.elapsed(),
+        )
+    }
+}
+
+/// Returns `true` if the test harness runs the tests that are `ignored`, or the ones that aren't,
+/// going by its `--ignored` and `--include-ignored` flags.
+fn harness_runs(ignored: bool) -> bool {
+    static FLAGS: OnceLock<(bool, bool)> = OnceLock::new();
+    let (only_ignored, include_ignored) = *FLAGS.get_or_init(|| {
+        let args: Vec<String> = env::args().collect();
+        (
+            args.iter().any(|arg| arg == "--ignored"),
+            args.iter().any(|arg| arg == "--include-ignored"),
+        )
+    });
+    include_ignored || ignored == only_ignored
+}
+
+/// A test function counted by a [`TestCounter`] that isn't listed in it, registered before `main`.
+pub struct RegisteredTest {
+    counter: &'static TestCounter,
+    count: usize,
+    tags: &'static [&'static str],
+    ignored: bool,
+}
+
+impl RegisteredTest {
+    /// `count` is the number of tests generated by the test function, and `ignored` whether it is
+    /// `#[ignore]`d.
+    pub const fn new(
+        counter: &'static TestCounter,
+        count: usize,
+        tags: &'static [&'static str],
+        ignored: bool,
+    ) -> Self {
+        RegisteredTest {
+            counter,
+            count,
+            tags,
+            ignored,
+        }
+    }
+}
+
+inventory::collect!(RegisteredTest);
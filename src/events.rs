@@ -0,0 +1,363 @@
+use std::cell::Cell;
+use std::env;
+use std::fmt::{Display, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{process, thread};
+
+use crate::outcome::TestOutcome;
+use crate::summary::ModuleSummary;
+use crate::test_info::{current_test, TestInfo};
+use crate::{junit, listener, timings};
+
+/// Path of a file to append a JSON line to whenever a hook or test starts or finishes, e.g.
+/// `TEST_ENV_HELPERS_EVENTS=target/test-events.jsonl`.
+const EVENTS_ENV: &str = "TEST_ENV_HELPERS_EVENTS";
+
+thread_local! {
+    /// The hook running on this thread, where it is defined and when it started.
+    static HOOK: Cell<Option<(&'static str, &'static str, Instant)>> = const { Cell::new(None) };
+    /// How long the hooks that ran on this thread since [`take_hooks_time`] was last called took.
+    static HOOKS_TIME: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Something that happened to a test or hook, which is reported to the file in
+/// `TEST_ENV_HELPERS_EVENTS`, to the JUnit report, to the timings report and to the registered
+/// listeners.
+pub(crate) enum Event<'a> {
+    TestStarted(&'a TestInfo),
+    /// A test finished, where an `outcome` of `None` means it was skipped. `failed_hook` is the
+    /// hook that was running when the test failed, if it failed in one. `in_hooks` is how much of
+    /// the `duration` was spent in its hooks.
+    TestFinished {
+        test: &'a TestInfo,
+        outcome: Option<&'a TestOutcome>,
+        duration: Duration,
+        in_hooks: Duration,
+        failed_hook: Option<&'static str>,
+    },
+    HookStarted {
+        hook: &'static str,
+        test: Option<&'a TestInfo>,
+    },
+    HookFinished {
+        hook: &'static str,
+        test: Option<&'a TestInfo>,
+        outcome: &'a TestOutcome,
+        duration: Duration,
+    },
+    /// The last test of a mod with `after_all` finished, and `after_all` has run.
+    ModuleFinished {
+        module_path: &'static str,
+        summary: &'a ModuleSummary,
+    },
+}
+
+fn file() -> Option<&'static Mutex<File>> {
+    static FILE: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+    FILE.get_or_init(|| {
+        let path = env::var_os(EVENTS_ENV).filter(|path| !path.is_empty())?;
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(Mutex::new(file)),
+            Err(err) => {
+                eprintln!(
+                    "can't open {} `{}`: {}",
+                    EVENTS_ENV,
+                    path.to_string_lossy(),
+                    err
+                );
+                None
+            }
+        }
+    })
+    .as_ref()
+}
+
+/// Returns `true` if events are reported, so callers can skip collecting what they'd report.
+pub(crate) fn enabled() -> bool {
+    file().is_some() || junit::enabled() || timings::enabled() || listener::enabled()
+}
+
+/// Reports `event` wherever events are reported.
+pub(crate) fn emit(event: Event) {
+    if let Some(file) = file() {
+        write_json(file, &event);
+    }
+    junit::record(&event);
+    timings::record(&event);
+    listener::notify(&event);
+}
+
+/// Writes `event` as a single JSON line. Each line is written with a single append, so tests
+/// running in parallel, even in different processes, can share the file.
+fn write_json(file: &Mutex<File>, event: &Event) {
+    let line = json(event);
+    let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+    if let Err(err) = file.write_all(line.as_bytes()) {
+        eprintln!("can't write to {}: {}", EVENTS_ENV, err);
+    }
+}
+
+/// Marks the start of `hook`, which runs until [`exit_hook`] is called or the test fails.
+/// `location` is where the hook is defined, e.g. `tests/db.rs:12`.
+pub fn enter_hook(hook: &'static str, location: &'static str) {
+    HOOK.with(|current| current.set(Some((hook, location, Instant::now()))));
+    if enabled() {
+        emit(Event::HookStarted {
+            hook,
+            test: current_test().as_ref(),
+        });
+    }
+}
+
+/// Marks the end of the hook started by [`enter_hook`].
+pub fn exit_hook() {
+    if let Some((hook, _, started)) = HOOK.with(Cell::take) {
+        let duration = started.elapsed();
+        HOOKS_TIME.with(|time| time.set(time.get() + duration));
+        if enabled() {
+            emit(Event::HookFinished {
+                hook,
+                test: current_test().as_ref(),
+                outcome: &TestOutcome::Passed,
+                duration,
+            });
+        }
+    }
+}
+
+/// Ends the hook that was still running when `test` finished with `outcome`, e.g. because it
+/// panicked. Returns the name and location of the hook if the test failed, since it failed in that
+/// hook.
+pub(crate) fn finish_hook(
+    test: &TestInfo,
+    outcome: &TestOutcome,
+) -> Option<(&'static str, &'static str)> {
+    let (hook, location, started) = HOOK.with(Cell::take)?;
+    let duration = started.elapsed();
+    HOOKS_TIME.with(|time| time.set(time.get() + duration));
+    emit(Event::HookFinished {
+        hook,
+        test: Some(test),
+        outcome,
+        duration,
+    });
+    Some((hook, location)).filter(|_| outcome.failed())
+}
+
+/// Returns how long the hooks that ran on this thread since the last call took.
+pub(crate) fn take_hooks_time() -> Duration {
+    HOOKS_TIME.with(Cell::take)
+}
+
+/// The name of the running test binary without the hash cargo adds to it, e.g. `my_tests`, which
+/// reports written for each test binary are named after.
+pub(crate) fn binary_name() -> String {
+    let exe = env::current_exe().ok();
+    let name = exe
+        .as_ref()
+        .and_then(|exe| exe.file_stem())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "tests".to_string());
+    match name.rsplit_once('-') {
+        Some((name, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            name.to_string()
+        }
+        _ => name,
+    }
+}
+
+/// The name reports list `test` under, with its arguments if it has any, e.g. `test_1(2)`.
+pub(crate) fn case_name(test: &TestInfo) -> String {
+    match test.args() {
+        [] => test.name().to_string(),
+        args => format!("{}({})", test.name(), args.join(", ")),
+    }
+}
+
+/// Adds `run` to the `runs` of a report, replacing an earlier run with the same `name`, so only
+/// the last run of a retried or repeated test is kept.
+pub(crate) fn keep_last_run<T>(runs: &mut Vec<T>, run: T, name: fn(&T) -> &str) {
+    match runs
+        .iter_mut()
+        .find(|existing| name(existing) == name(&run))
+    {
+        Some(existing) => *existing = run,
+        None => runs.push(run),
+    }
+}
+
+/// Calls `write` when the test binary exits, after all of its tests have finished, so reports are
+/// written once instead of after every test. Like the test harness itself, a test binary killed by
+/// a signal or an abort doesn't write them.
+pub(crate) fn at_exit(write: extern "C" fn()) {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+    }
+    // SAFETY: `atexit` only stores the callback, which the C runtime calls when the process exits,
+    // whether `main` returned or `process::exit` was called.
+    if unsafe { atexit(write) } != 0 {
+        eprintln!("can't register the reports to write when the tests finish");
+    }
+}
+
+/// Replaces the report at `path` with `contents`. The report is written to a temporary file first,
+/// so it is never seen half written.
+pub(crate) fn write_report(path: &Path, contents: &str) {
+    let tmp = path.with_extension("tmp");
+    if let Err(err) = fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path)) {
+        eprintln!("can't write the report `{}`: {}", path.display(), err);
+    }
+}
+
+fn json(event: &Event) -> String {
+    let mut line = JsonLine::default();
+    match event {
+        Event::TestStarted(test) => {
+            line.field("event", "test_started");
+            line.test(test);
+            line.strings("args", test.args());
+        }
+        Event::TestFinished {
+            test,
+            outcome,
+            duration,
+            in_hooks,
+            failed_hook,
+        } => {
+            line.field("event", "test_finished");
+            line.test(test);
+            line.outcome(*outcome);
+            line.raw("duration_ms", millis(*duration));
+            line.raw("hooks_ms", millis(*in_hooks));
+            if let Some(hook) = failed_hook {
+                line.field("failed_hook", hook);
+            }
+        }
+        Event::HookStarted { hook, test } => {
+            line.field("event", "hook_started");
+            line.field("hook", hook);
+            if let Some(test) = test {
+                line.test(test);
+            }
+        }
+        Event::HookFinished {
+            hook,
+            test,
+            outcome,
+            duration,
+        } => {
+            line.field("event", "hook_finished");
+            line.field("hook", hook);
+            if let Some(test) = test {
+                line.test(test);
+            }
+            line.outcome(Some(outcome));
+            line.raw("duration_ms", millis(*duration));
+        }
+        Event::ModuleFinished {
+            module_path,
+            summary,
+        } => {
+            line.field("event", "module_finished");
+            line.field("module_path", module_path);
+            line.raw("passed", summary.passed());
+            line.raw("failed", summary.failed());
+            line.raw("skipped", summary.skipped());
+            line.raw("duration_ms", millis(summary.duration()));
+        }
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    line.raw("timestamp_ms", time.as_millis());
+    line.raw("pid", process::id());
+    if let Some(name) = thread::current().name() {
+        line.field("thread", name);
+    }
+    line.finish()
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// A JSON object being written on a single line.
+#[derive(Default)]
+struct JsonLine(String);
+
+impl JsonLine {
+    fn key(&mut self, key: &str) {
+        self.0.push(if self.0.is_empty() { '{' } else { ',' });
+        push_string(&mut self.0, key);
+        self.0.push(':');
+    }
+
+    fn field(&mut self, key: &str, value: &str) {
+        self.key(key);
+        push_string(&mut self.0, value);
+    }
+
+    fn raw(&mut self, key: &str, value: impl Display) {
+        self.key(key);
+        let _ = write!(self.0, "{}", value);
+    }
+
+    fn strings(&mut self, key: &str, values: &[impl AsRef<str>]) {
+        self.key(key);
+        self.0.push('[');
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.0.push(',');
+            }
+            push_string(&mut self.0, value.as_ref());
+        }
+        self.0.push(']');
+    }
+
+    fn test(&mut self, test: &TestInfo) {
+        self.field("test", &test.full_name());
+        self.field("module_path", test.module_path());
+        self.field("name", test.name());
+        self.strings("tags", test.tags());
+    }
+
+    fn outcome(&mut self, outcome: Option<&TestOutcome>) {
+        let (name, message) = match outcome {
+            None => ("skipped", None),
+            Some(TestOutcome::Passed) => ("passed", None),
+            Some(TestOutcome::Panicked(message)) => ("panicked", Some(message)),
+            Some(TestOutcome::Failed(message)) => ("failed", Some(message)),
+        };
+        self.field("outcome", name);
+        if let Some(message) = message {
+            self.field("message", message);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.0.push_str("}\n");
+        self.0
+    }
+}
+
+fn push_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
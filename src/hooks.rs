@@ -0,0 +1,74 @@
+use std::sync::Once;
+
+use crate::counter::TestCounter;
+use crate::outcome::TestOutcome;
+use crate::summary::ModuleSummary;
+
+/// Anything `before_each` can return, which is held until the end of the test and then dropped.
+pub trait Guard {}
+
+impl<T> Guard for T {}
+
+/// The hooks of a mod with `#[hooks]` applied, which tests anywhere can opt into with
+/// `#[with_hooks(path::to::the_mod)]`. Unlike the mod attributes, the hooks are called as functions
+/// rather than having their bodies inserted into the tests.
+pub struct Hooks {
+    before_all: Option<fn()>,
+    before_each: Option<fn() -> Box<dyn Guard>>,
+    after_each: Option<fn(&TestOutcome, bool)>,
+    after_all: Option<fn(&ModuleSummary)>,
+    before_all_once: Once,
+    counter: TestCounter,
+}
+
+impl Hooks {
+    /// `module_path` is the path of the mod the hooks are declared in.
+    pub const fn new(
+        module_path: &'static str,
+        before_all: Option<fn()>,
+        before_each: Option<fn() -> Box<dyn Guard>>,
+        after_each: Option<fn(&TestOutcome, bool)>,
+        after_all: Option<fn(&ModuleSummary)>,
+    ) -> Self {
+        Hooks {
+            before_all,
+            before_each,
+            after_each,
+            after_all,
+            before_all_once: Once::new(),
+            counter: TestCounter::new(module_path, &[]),
+        }
+    }
+
+    /// Counts the tests using these hooks, which register themselves with it.
+    pub const fn counter(&'static self) -> &'static TestCounter {
+        &self.counter
+    }
+
+    pub fn before_all(&self) {
+        if let Some(before_all) = self.before_all {
+            self.before_all_once.call_once(before_all);
+        }
+    }
+
+    /// Runs `before_each`, returning the guard it returned for the test to hold until it finishes.
+    pub fn before_each(&self) -> Option<Box<dyn Guard>> {
+        self.before_each.map(|before_each| before_each())
+    }
+
+    /// Runs `after_each` for a test that ended with `outcome`. Hooks that don't take the outcome
+    /// return early for tests that `panicked`, even if they were expected to.
+    pub fn after_each(&self, outcome: &TestOutcome, panicked: bool) {
+        if let Some(after_each) = self.after_each {
+            after_each(outcome, panicked);
+        }
+    }
+
+    /// Marks one test as finished and runs `after_all` if it was the last one of the tests using
+    /// these hooks. Runs that will be retried or repeated don't count.
+    pub fn finish_one(&self, final_run: bool, passed: bool) {
+        if let Some(after_all) = self.after_all {
+            self.counter.finish(final_run, passed, after_all);
+        }
+    }
+}
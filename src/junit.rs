@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::events::{self, Event};
+use crate::outcome::TestOutcome;
+
+/// Directory to write a JUnit XML report to for each test binary, e.g.
+/// `TEST_ENV_HELPERS_JUNIT=target/junit`. `cargo test` runs every test binary in a process of its
+/// own, so each one writes a file named after itself.
+const JUNIT_ENV: &str = "TEST_ENV_HELPERS_JUNIT";
+
+/// A test that `#[skip]` removed, which the report still lists as skipped.
+pub struct SkippedTest {
+    module_path: &'static str,
+    name: &'static str,
+}
+
+impl SkippedTest {
+    pub const fn new(module_path: &'static str, name: &'static str) -> Self {
+        SkippedTest { module_path, name }
+    }
+}
+
+inventory::collect!(SkippedTest);
+
+struct TestCase {
+    name: String,
+    duration: Duration,
+    result: CaseResult,
+}
+
+enum CaseResult {
+    Passed,
+    Skipped,
+    /// The test itself failed, where `kind` is how, `panicked` or `failed`.
+    Failure {
+        kind: &'static str,
+        message: String,
+    },
+    /// One of the hooks of the test failed.
+    HookError {
+        hook: &'static str,
+        message: String,
+    },
+}
+
+struct Report {
+    path: PathBuf,
+    name: String,
+    /// The tests of each mod, in the order they finished.
+    suites: BTreeMap<&'static str, Vec<TestCase>>,
+}
+
+fn report() -> Option<&'static Mutex<Report>> {
+    static REPORT: OnceLock<Option<Mutex<Report>>> = OnceLock::new();
+    REPORT
+        .get_or_init(|| {
+            let dir = PathBuf::from(env::var_os(JUNIT_ENV).filter(|dir| !dir.is_empty())?);
+            if let Err(err) = fs::create_dir_all(&dir) {
+                eprintln!("can't create {} `{}`: {}", JUNIT_ENV, dir.display(), err);
+                return None;
+            }
+            let name = events::binary_name();
+            events::at_exit(write);
+            let mut suites: BTreeMap<_, Vec<_>> = BTreeMap::new();
+            for test in inventory::iter::<SkippedTest> {
+                suites.entry(test.module_path).or_default().push(TestCase {
+                    name: test.name.to_string(),
+                    duration: Duration::ZERO,
+                    result: CaseResult::Skipped,
+                });
+            }
+            Some(Mutex::new(Report {
+                path: dir.join(format!("{}.xml", name)),
+                name,
+                suites,
+            }))
+        })
+        .as_ref()
+}
+
+/// Returns `true` if a JUnit report is written.
+pub(crate) fn enabled() -> bool {
+    report().is_some()
+}
+
+/// Adds the test that finished in `event` to the report, which is written when the test binary
+/// exits. Only the last run of a retried or repeated test is kept.
+pub(crate) fn record(event: &Event) {
+    let (test, outcome, duration, failed_hook) = match event {
+        Event::TestFinished {
+            test,
+            outcome,
+            duration,
+            failed_hook,
+            ..
+        } => (test, outcome, duration, failed_hook),
+        _ => return,
+    };
+    let report = match report() {
+        Some(report) => report,
+        None => return,
+    };
+    let result = match (outcome, failed_hook) {
+        (None, _) => CaseResult::Skipped,
+        (Some(TestOutcome::Passed), _) => CaseResult::Passed,
+        (Some(TestOutcome::Panicked(message) | TestOutcome::Failed(message)), Some(hook)) => {
+            CaseResult::HookError {
+                hook,
+                message: message.clone(),
+            }
+        }
+        (Some(TestOutcome::Panicked(message)), None) => CaseResult::Failure {
+            kind: "panicked",
+            message: message.clone(),
+        },
+        (Some(TestOutcome::Failed(message)), None) => CaseResult::Failure {
+            kind: "failed",
+            message: message.clone(),
+        },
+    };
+    let case = TestCase {
+        name: events::case_name(test),
+        duration: *duration,
+        result,
+    };
+
+    let mut report = report.lock().unwrap_or_else(|err| err.into_inner());
+    let cases = report.suites.entry(test.module_path()).or_default();
+    events::keep_last_run(cases, case, |case| &case.name);
+}
+
+extern "C" fn write() {
+    if let Some(report) = report() {
+        let report = report.lock().unwrap_or_else(|err| err.into_inner());
+        events::write_report(&report.path, &report.render());
+    }
+}
+
+/// The number of tests, failures, hook errors and skipped tests among `cases`, and how long they
+/// took.
+fn totals<'a>(cases: impl Iterator<Item = &'a TestCase>) -> (usize, usize, usize, usize, f64) {
+    cases.fold(
+        (0, 0, 0, 0, 0.0),
+        |(tests, failures, errors, skipped, time), case| {
+            let (failure, error, skip) = match case.result {
+                CaseResult::Passed => (0, 0, 0),
+                CaseResult::Failure { .. } => (1, 0, 0),
+                CaseResult::HookError { .. } => (0, 1, 0),
+                CaseResult::Skipped => (0, 0, 1),
+            };
+            (
+                tests + 1,
+                failures + failure,
+                errors + error,
+                skipped + skip,
+                time + case.duration.as_secs_f64(),
+            )
+        },
+    )
+}
+
+impl Report {
+    fn render(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let (tests, failures, errors, skipped, time) =
+            totals(self.suites.values().flat_map(|cases| cases.iter()));
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape(&self.name),
+            tests,
+            failures,
+            errors,
+            skipped,
+            time
+        );
+        for (module_path, cases) in &self.suites {
+            let (tests, failures, errors, skipped, time) = totals(cases.iter());
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                escape(module_path),
+                tests,
+                failures,
+                errors,
+                skipped,
+                time
+            );
+            for case in cases {
+                let _ = write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                    escape(&case.name),
+                    escape(module_path),
+                    case.duration.as_secs_f64()
+                );
+                let (element, kind, message) = match &case.result {
+                    CaseResult::Passed => {
+                        xml.push_str("/>\n");
+                        continue;
+                    }
+                    CaseResult::Skipped => {
+                        xml.push_str(">\n      <skipped/>\n    </testcase>\n");
+                        continue;
+                    }
+                    CaseResult::Failure { kind, message } => ("failure", *kind, message),
+                    CaseResult::HookError { hook, message } => ("error", *hook, message),
+                };
+                let summary = message.lines().next().unwrap_or_default();
+                let _ = write!(
+                    xml,
+                    ">\n      <{0} type=\"{1}\" message=\"{2}\">{3}</{0}>\n    </testcase>\n",
+                    element,
+                    kind,
+                    escape(summary),
+                    escape(message)
+                );
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Escapes `text` for use in XML, replacing the control characters XML doesn't allow.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+            c if c.is_control() => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -3,18 +3,30 @@
 //! functionality. Also includes a `skip` macro that mimics the [skip](https://jestjs.io/docs/api#testskipname-fn)
 //! functionality in Jest.
 //!
-//! There are currently five macros provided: `after_all`,
-//! `after_each`, `before_all`, `before_each`, and `skip`. I would like to implement `only` to
-//! match [Jest's only](https://jestjs.io/docs/api#testonlyname-fn-timeout) functionality. I'm
-//! unsure of a great way to do that currently, however.
+//! There are currently twenty macros provided: `after`, `after_all`, `after_each`, `before`,
+//! `before_all`, `before_each`, `hooked`, `hooks`, `lifecycle`, `listener`, `max_concurrency`,
+//! `no_hooks`, `repeat`, `retry`, `serial`, `skip`, `skip_after_each`, `skip_before_each`, `tag`,
+//! and `with_hooks`. I would like to implement `only` to match
+//! [Jest's only](https://jestjs.io/docs/api#testonlyname-fn-timeout) functionality. I'm unsure of
+//! a great way to do that currently, however.
 //!
 //! ## Getting Started
 //! Using these macros is fairly simple. The four after/before functions all require a function
 //! with the same name as the attribute and are only valid when applied to a mod. They are all used
 //! like in the below example. Replace `before_each` with whichever method you want to use. The
-//! code in the matching function will be inserted into every fn in the containing mod that has an
-//! attribute with the word "test" in it. This is to allow for use with not just normal `#[test]`
-//! attributes, but also other flavors like `#[tokio::test]` and `#[test_case(0)]`.
+//! code in the matching function will be inserted into every test in the containing mod. Tests are
+//! recognized by their attribute: `#[test]`, `#[tokio::test]`, `#[async_std::test]`,
+//! `#[test_log::test]`, `#[actix_rt::test]`, `#[sqlx::test]`, `#[wasm_bindgen_test]`,
+//! `#[test_case(0)]` and `#[rstest]` are all known, and `after_all` counts every `#[test_case]` and
+//! every `#[case]`/`#[values]` combination of an `#[rstest]` as a separate test. Other test
+//! attributes can be registered with `test_attrs`, e.g.
+//! `#[before_each(test_attrs = [my_crate::it])]` or `#[retry(3, test_attrs = [my_crate::it])]`;
+//! pass the same list to every hook, `retry`, `repeat`, `serial`, `max_concurrency`, `tag` and
+//! `skip` on the module. A test can opt out of the hooks with `#[no_hooks]`, `#[skip_before_each]`
+//! or `#[skip_after_each]`. A panic in `before_each` or `after_each` fails the test with a message
+//! naming the hook, the test and where the hook is defined, e.g.
+//! ``before_each hook failed for `my_tests::test_2` (hook at src/lib.rs:12): ...``, so it isn't
+//! mistaken for a failure of the test itself.
 //! ```
 //! #[cfg(test)]
 //! use test_env_helpers::*;
@@ -32,6 +44,112 @@
 //! }
 //! ```
 //!
+//! `before_each` and `after_each` can take a `&TestInfo` parameter to find out which test they run
+//! for, e.g. to name a temp dir after it. Test bodies and the helpers they call get the same info
+//! from `current_test()`.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[before_each]
+//! #[cfg(test)]
+//! mod my_tests{
+//!     use test_env_helpers::TestInfo;
+//!     fn before_each(info: &TestInfo){println!("I'm about to run {}", info.full_name())}
+//!     #[test]
+//!     fn test_1(){}
+//! }
+//! ```
+//!
+//! `before_each` can return a value, like a lock guard, which is held until the test has finished.
+//!
+//! Setup that happens inside `before_each` or the test itself can register its teardown with
+//! `cleanup(|| ..)`. The cleanups of a test run at its end, last one first and before
+//! `after_each`, whether the test passed or panicked halfway through.
+//!
+//! When stacking several of the hook attributes, the order of the attributes decides how their code
+//! is nested. `lifecycle` instead applies whichever of the four functions the mod defines in one
+//! pass: `before_all`, `before_each`, the test, `after_each`, and finally `after_all`.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[lifecycle]
+//! #[cfg(test)]
+//! mod my_tests{
+//!     fn before_all(){println!("I get run once, first")}
+//!     fn after_each(){println!("I get run after every test")}
+//!     #[test]
+//!     fn test_1(){}
+//! }
+//! ```
+//!
+//! A single test can run its own setup and teardown with `before` and `after`, which name the
+//! functions to run. They run inside the hooks of the mod, right around the test itself.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[before_each]
+//! #[cfg(test)]
+//! mod my_tests{
+//!     fn before_each(){println!("I get run first")}
+//!     fn seed_users(){println!("I get run second, only for `test_1`")}
+//!     #[before(seed_users)]
+//!     #[test]
+//!     fn test_1(){}
+//! }
+//! ```
+//!
+//! The attributes can't be applied to a mod whose body is in another file, like
+//! `#[cfg(test)] mod tests;`. Instead, the hooks can be declared in a mod with `hooks` applied and
+//! each test in the other file opts into them with `with_hooks`.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[hooks]
+//! #[cfg(test)]
+//! mod db_hooks{
+//!     fn before_each(){println!("I get run before every test using these hooks")}
+//! }
+//!
+//! #[cfg(test)]
+//! mod tests{
+//!     use test_env_helpers::*;
+//!
+//!     #[with_hooks(super::db_hooks)]
+//!     #[test]
+//!     fn test_1(){}
+//! }
+//! ```
+//!
+//! Tests generated by a `macro_rules!` macro defined in the mod aren't seen by the attributes, so
+//! they are marked with `hooked` in the macro to get the hooks of the mod.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[before_each]
+//! #[cfg(test)]
+//! mod my_tests{
+//!     fn before_each(){println!("I get run before every generated test")}
+//!     macro_rules! a_test {
+//!         ($name:ident) => {
+//!             #[hooked]
+//!             #[test]
+//!             fn $name(){}
+//!         };
+//!     }
+//!     a_test!(test_1);
+//! }
+//! ```
+//!
 //! The `skip` macro is valid on either a mod or an individual test and will remove the mod or test
 //! it is applied to. You can use it to skip tests that aren't working correctly or that you don't
 //! want to run for some reason.
@@ -56,487 +174,127 @@
 //!     fn test_3(){}
 //! }
 //! ```
+//!
+//! The `retry` macro is valid on either a mod or an individual test and will re-run a failing test
+//! up to the given number of times, including any `before_each`/`after_each` code that has been
+//! inserted into it. Setting the `TEST_ENV_HELPERS_NO_RETRY` environment variable disables retries
+//! so that flaky tests fail on their first attempt.
+//!
+//! ```
+//! #[cfg(test)]
+//! use test_env_helpers::*;
+//!
+//! #[retry(3)]
+//! #[cfg(test)]
+//! mod my_tests{
+//!     #[test]
+//!     fn sometimes_broken_test(){}
+//!     #[retry(5)]
+//!     #[test]
+//!     fn often_broken_test(){}
+//! }
+//! ```
+//!
+//! The `repeat` macro runs a test many times in a row to shake out race conditions, failing on the
+//! first iteration that fails. Setting the `TEST_ENV_HELPERS_REPEAT` environment variable repeats
+//! every test in a mod that has any of the before/after hooks applied.
+//!
+//! The `serial` macro keeps the tests of a mod, or of a named group spanning several mods, from
+//! running at the same time as each other. The `max_concurrency` macro does the same, but allows up
+//! to a given number of tests to run at once.
+//!
+//! The `tag` macro tags tests so that hooks can be limited to some of them, e.g.
+//! `#[before_each(tags = [db])]`, and so that they can be selected at runtime with the `TEST_TAGS`
+//! environment variable, e.g. `TEST_TAGS=db,!slow cargo test`.
+//!
+//! ## Reporting
+//! Setting the `TEST_ENV_HELPERS_EVENTS` environment variable to a file path, e.g.
+//! `TEST_ENV_HELPERS_EVENTS=target/test-events.jsonl cargo test`, appends a JSON line to that file
+//! whenever a test with any of the hooks or tags applied, or one of its hooks, starts or finishes.
+//! Each line has an `event` (`test_started`, `test_finished`, `hook_started` or `hook_finished`),
+//! the `test`, `module_path`, `name` and `tags` of the test, and, for finished events, the
+//! `outcome` (`passed`, `panicked`, `failed` or `skipped`), any `message` and the `duration_ms`.
+//! A test that failed while one of its hooks was running names that hook as its `failed_hook`.
+//! Once the last test of a mod with `after_all` has finished, a `module_finished` line has the
+//! `passed`, `failed` and `skipped` counts of the mod. Lines are appended whole, so tests running
+//! in parallel, even from several test binaries, can share the file.
+//!
+//! Setting `TEST_ENV_HELPERS_JUNIT` to a directory, e.g. `TEST_ENV_HELPERS_JUNIT=target/junit`,
+//! writes a JUnit XML report for each test binary to that directory, named after the binary. It has
+//! a `<testsuite>` for each mod and a `<testcase>` for each test with any of the hooks or tags
+//! applied. Tests that fail get a `<failure>`, unless they failed in one of their hooks, which gets
+//! an `<error>` with the name of the hook as its `type` instead. Tests skipped with `#[skip]` or
+//! `TEST_TAGS` are listed as `<skipped/>`. The report is written when the test binary exits.
+//!
+//! Setting `TEST_ENV_HELPERS_TIMINGS` to a directory writes a report for each test binary to that
+//! directory with how long the tests of each mod took and how much of that went to each of their
+//! hooks, listing the slowest tests and hooks first. The reports are written when the test binaries
+//! exit. Setting `TEST_ENV_HELPERS_SLOW_MS`, e.g. to `500`, prints a warning for every test that
+//! takes longer than that many milliseconds, hooks included.
+//!
+//! Other reporters can be plugged in by implementing [`TestListener`] and registering it with
+//! `#[listener]` on a static. Its methods are called whenever a test or hook starts or ends, and
+//! when the last test of a mod with `after_all` has finished.
+//!
+//! ## Logs
+//! Tests with any of the hooks or tags applied capture the `tracing` events logged on their thread,
+//! including by their hooks, and print them only if the test fails. The events are captured by a
+//! subscriber set as the default of the test's thread while it runs, unless the tests installed a
+//! global subscriber of their own. [`captured_logs`] returns what the current test has logged so
+//! far, e.g. to check for a warning with
+//! `captured_logs().assert_contains(Level::WARN, "retrying")`. This can be turned off with the
+//! `tracing` feature, which is enabled by default.
+//!
+//! The `log` records of the tests aren't captured by default. They are with the opt-in `log`
+//! feature, e.g. `test-env-helpers = { version = "0.2", features = ["log"] }`, which installs a
+//! global logger, and enables every level of `log`, the first time a test starts.
 
-extern crate proc_macro;
-mod utils;
-
-use crate::utils::traverse_use_item;
-
-use proc_macro::TokenStream;
-use quote::quote;
-use syn::Stmt;
-use syn::Item;
-use syn::parse_quote;
-use syn::parse_macro_input;
-
-/// Will run the code in the matching `after_all` function exactly once when all of the tests have
-/// run. This works by counting the number of `#[test]` attributes and decrementing a counter at
-/// the beginning of every test. Once the counter reaches 0, it will run the code in `after_all`.
-/// It uses [std::sync::Once](https://doc.rust-lang.org/std/sync/struct.Once.html) internally
-/// to ensure that the code is run at maximum one time.
-///
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[after_all]
-/// #[cfg(test)]
-/// mod my_tests{
-///     fn after_all(){println!("I only get run once at the very end")}
-///     #[test]
-///     fn test_1(){}
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn after_all(_metadata: TokenStream, input: TokenStream) -> TokenStream {
-    let input: Item = match parse_macro_input!(input as Item) {
-        Item::Mod(mut m) => {
-            let (brace, items) = m.content.unwrap();
-            let (after_all_fn, everything_else): (Vec<Item>, Vec<Item>) =
-                items.into_iter().partition(|t| match t {
-                    Item::Fn(f) => f.sig.ident == "after_all",
-                    _ => false,
-                });
-            let after_all_fn_block = if after_all_fn.len() != 1 {
-                panic!("The `after_all` macro attribute requires a single function named `after_all` in the body of the module it is called on.")
-            } else {
-                match after_all_fn.into_iter().next().unwrap() {
-                    Item::Fn(f) => f.block,
-                    _ => unreachable!(),
-                }
-            };
-            let after_all_if: Stmt = parse_quote! {
-                if REMAINING_TESTS.fetch_sub(1, Ordering::SeqCst) == 1 {
-                    AFTER_ALL.call_once(|| {
-                        #after_all_fn_block
-                    });
-                }
-            };
-            let resume: Stmt = parse_quote! {
-                if let Err(err) = result {
-                    panic::resume_unwind(err);
-                }
-            };
-            let mut count: usize = 0;
-            let mut has_once: bool = false;
-            let mut has_atomic_usize: bool = false;
-            let mut has_ordering: bool = false;
-            let mut has_panic: bool = false;
-
-            let mut e: Vec<Item> = everything_else
-                .into_iter()
-                .map(|t| match t {
-                    Item::Fn(mut f) => {
-                        let test_count = f
-                            .attrs
-                            .iter()
-                            .filter(|attr| {
-                                attr.path
-                                    .segments
-                                    .iter()
-                                    .any(|segment| segment.ident.to_string().contains("test"))
-                            })
-                            .count();
-                        if test_count > 0 {
-                            count += test_count;
-                            let block = f.block.clone();
-                            let catch_unwind: Stmt = parse_quote! {
-                                let result = panic::catch_unwind(|| {
-                                    #block
-                                });
-                            };
-                            f.block.stmts = vec![catch_unwind, after_all_if.clone(), resume.clone()];
-                            Item::Fn(f)
-                        } else {
-                            Item::Fn(f)
-                        }
-                    }
-                    Item::Use(use_stmt) => {
-                        if traverse_use_item(&use_stmt.tree, vec!["std", "sync", "Once"]).is_some()
-                        {
-                            has_once = true;
-                        }
-                        if traverse_use_item(
-                            &use_stmt.tree,
-                            vec!["std", "sync", "atomic", "AtomicUsize"],
-                        )
-                        .is_some()
-                        {
-                            has_atomic_usize = true;
-                        }
-                        if traverse_use_item(
-                            &use_stmt.tree,
-                            vec!["std", "sync", "atomic", "Ordering"],
-                        )
-                        .is_some()
-                        {
-                            has_ordering = true;
-                        }
-                        if traverse_use_item(
-                            &use_stmt.tree,
-                            vec!["std", "panic"],
-                        )
-                        .is_some() {
-                            has_panic = true;
-                        }
-                        Item::Use(use_stmt)
-                    }
-                    el => el,
-                })
-                .collect();
-
-            let use_once: Item = parse_quote!(
-                use std::sync::Once;
-            );
-            let use_atomic_usize: Item = parse_quote!(
-                use std::sync::atomic::AtomicUsize;
-            );
-            let use_ordering: Item = parse_quote!(
-                use std::sync::atomic::Ordering;
-            );
-            let use_panic: Item = parse_quote!(
-                use std::panic;
-            );
-            let static_once: Item = parse_quote!(
-                static AFTER_ALL: Once = Once::new();
-            );
-            let static_count: Item = parse_quote!(
-                static REMAINING_TESTS: AtomicUsize = AtomicUsize::new(#count);
-            );
-
-            let mut once_content = vec![];
-
-            if !has_once {
-                once_content.push(use_once);
-            }
-            if !has_atomic_usize {
-                once_content.push(use_atomic_usize);
-            }
-            if !has_ordering {
-                once_content.push(use_ordering);
-            }
-            if !has_panic {
-                once_content.push(use_panic);
-            }
-            once_content.append(&mut vec![static_once, static_count]);
-            once_content.append(&mut e);
-
-            m.content = Some((brace, once_content));
-            Item::Mod(m)
-        }
-        _ => {
-            panic!("The `after_all` macro attribute is only valid when called on a module.")
-        }
-    };
-    TokenStream::from(quote! (#input))
-}
-
-/// Will run the code in the matching `after_each` function at the end of every `#[test]` function.
-/// Useful if you want to cleanup after a test or reset some external state. If the test panics,
-/// this code will not be run. If you need something that is infallible, you should use
-/// `before_each` instead.
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[after_each]
-/// #[cfg(test)]
-/// mod my_tests{
-///     fn after_each(){println!("I get run at the very end of each function")}
-///     #[test]
-///     fn test_1(){}
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn after_each(_metadata: TokenStream, input: TokenStream) -> TokenStream {
-    let input: Item = match parse_macro_input!(input as Item) {
-        Item::Mod(mut m) => {
-            let (brace, items) = m.content.unwrap();
-            let (after_each_fn, everything_else): (Vec<Item>, Vec<Item>) =
-                items.into_iter().partition(|t| match t {
-                    Item::Fn(f) => f.sig.ident == "after_each",
-                    _ => false,
-                });
-            let after_each_fn_block = if after_each_fn.len() != 1 {
-                panic!("The `after_each` macro attribute requires a single function named `after_each` in the body of the module it is called on.")
-            } else {
-                match after_each_fn.into_iter().next().unwrap() {
-                    Item::Fn(f) => f.block,
-                    _ => unreachable!(),
-                }
-            };
-
-            let e: Vec<Item> = everything_else
-                .into_iter()
-                .map(|t| match t {
-                    Item::Fn(mut f) => {
-                        if f.attrs.iter().any(|attr| {
-                            attr.path
-                                .segments
-                                .iter()
-                                .any(|segment| segment.ident.to_string().contains("test"))
-                        }) {
-                            f.block.stmts.append(&mut after_each_fn_block.stmts.clone());
-                            Item::Fn(f)
-                        } else {
-                            Item::Fn(f)
-                        }
-                    }
-                    e => e,
-                })
-                .collect();
-            m.content = Some((brace, e));
-            Item::Mod(m)
-        }
-
-        _ => {
-            panic!("The `after_each` macro attribute is only valid when called on a module.")
-        }
-    };
-    TokenStream::from(quote! {#input})
-}
-
-/// Will run the code in the matching `before_all` function exactly once at the very beginning of a
-/// test run. It uses [std::sync::Once](https://doc.rust-lang.org/std/sync/struct.Once.html) internally
-/// to ensure that the code is run at maximum one time. Useful for setting up some external state
-/// that will be reused in multiple tests.
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[before_all]
-/// #[cfg(test)]
-/// mod my_tests{
-///     fn before_all(){println!("I get run at the very beginning of the test suite")}
-///     #[test]
-///     fn test_1(){}
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn before_all(_metadata: TokenStream, input: TokenStream) -> TokenStream {
-    let input: Item = match parse_macro_input!(input as Item) {
-        Item::Mod(mut m) => {
-            let (brace, items) = m.content.unwrap();
-            let (before_all_fn, everything_else): (Vec<Item>, Vec<Item>) =
-                items.into_iter().partition(|t| match t {
-                    Item::Fn(f) => f.sig.ident == "before_all",
-                    _ => false,
-                });
-            let before_all_fn_block = if before_all_fn.len() != 1 {
-                panic!("The `before_all` macro attribute requires a single function named `before_all` in the body of the module it is called on.")
-            } else {
-                match before_all_fn.into_iter().next().unwrap() {
-                    Item::Fn(f) => f.block,
-                    _ => unreachable!(),
-                }
-            };
-            let q: Stmt = parse_quote! {
-                BEFORE_ALL.call_once(|| {
-                    #before_all_fn_block
-                });
-            };
-
-            let mut has_once: bool = false;
-            let mut e: Vec<Item> = everything_else
-                .into_iter()
-                .map(|t| match t {
-                    Item::Fn(mut f) => {
-                        if f.attrs.iter().any(|attr| {
-                            attr.path
-                                .segments
-                                .iter()
-                                .any(|segment| segment.ident.to_string().contains("test"))
-                        }) {
-                            let mut stmts = vec![q.clone()];
-                            stmts.append(&mut f.block.stmts);
-                            f.block.stmts = stmts;
-                            Item::Fn(f)
-                        } else {
-                            Item::Fn(f)
-                        }
-                    }
-                    Item::Use(use_stmt) => {
-                        if traverse_use_item(&use_stmt.tree, vec!["std", "sync", "Once"]).is_some()
-                        {
-                            has_once = true;
-                        }
-                        Item::Use(use_stmt)
-                    }
-                    e => e,
-                })
-                .collect();
-            let use_once: Item = parse_quote!(
-                use std::sync::Once;
-            );
-            let static_once: Item = parse_quote!(
-                static BEFORE_ALL: Once = Once::new();
-            );
-
-            let mut once_content = vec![];
-            if !has_once {
-                once_content.push(use_once);
-            }
-            once_content.push(static_once);
-            once_content.append(&mut e);
-
-            m.content = Some((brace, once_content));
-            Item::Mod(m)
-        }
-
-        _ => {
-            panic!("The `before_all` macro attribute is only valid when called on a module.")
-        }
-    };
-    TokenStream::from(quote! (#input))
-}
+// The examples are wrapped in `#[cfg(test)]` like real test modules would be.
+#![allow(clippy::test_attr_in_doctest)]
 
-/// Will run the code in the matching `before_each` function at the beginning of every test. Useful
-/// to reset state to ensure that a test has a clean slate.
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[before_each]
-/// #[cfg(test)]
-/// mod my_tests{
-///     fn before_each(){println!("I get run at the very beginning of every test")}
-///     #[test]
-///     fn test_1(){}
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-///
-/// Can be used to reduce the amount of boilerplate setup code that needs to be copied into each test.
-/// For example, if you need to ensure that tests in a single test suite are not run in parallel, this can
-/// easily be done with a [Mutex](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
-/// However, remembering to copy and paste the code to acquire a lock on the `Mutex` in every test
-/// is tedious and error prone.
-/// ```
-/// #[cfg(test)]
-/// mod without_before_each{
-///     lazy_static! {
-///         static ref MTX: Mutex<()> = Mutex::new(());
-///     }
-///     #[test]
-///     fn test_1(){let _m = MTX.lock();}
-///     #[test]
-///     fn test_2(){let _m = MTX.lock();}
-///     #[test]
-///     fn test_3(){let _m = MTX.lock();}
-/// }
-/// ```
-/// Using `before_each` removes the need to copy and paste so much and makes making changes easier
-/// because they only need to be made in a single location instead of once for every test.
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[before_each]
-/// #[cfg(test)]
-/// mod with_before_each{
-///     lazy_static! {
-///         static ref MTX: Mutex<()> = Mutex::new(());
-///     }
-///     fn before_each(){let _m = MTX.lock();}
-///     #[test]
-///     fn test_1(){}
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn before_each(_metadata: TokenStream, input: TokenStream) -> TokenStream {
-    let input: Item = match parse_macro_input!(input as Item) {
-        Item::Mod(mut m) => {
-            let (brace, items) = m.content.unwrap();
-            let (before_each_fn, everything_else): (Vec<Item>, Vec<Item>) =
-                items.into_iter().partition(|t| match t {
-                    Item::Fn(f) => f.sig.ident == "before_each",
-                    _ => false,
-                });
-            let before_each_fn_block = if before_each_fn.len() != 1 {
-                panic!("The `before_each` macro attribute requires a single function named `before_each` in the body of the module it is called on.")
-            } else {
-                match before_each_fn.into_iter().next().unwrap() {
-                    Item::Fn(f) => f.block,
-                    _ => unreachable!(),
-                }
-            };
+mod catch_unwind;
+mod cleanup;
+mod counter;
+mod events;
+mod hooks;
+mod junit;
+mod listener;
+mod logs;
+mod outcome;
+mod repeat;
+mod rerun;
+mod retry;
+mod semaphore;
+mod summary;
+mod tags;
+mod test_info;
+mod timings;
 
-            let e: Vec<Item> = everything_else
-                .into_iter()
-                .map(|t| match t {
-                    Item::Fn(mut f) => {
-                        if f.attrs.iter().any(|attr| {
-                            attr.path
-                                .segments
-                                .iter()
-                                .any(|segment| segment.ident.to_string().contains("test"))
-                        }) {
-                            let mut b = before_each_fn_block.stmts.clone();
-                            b.append(&mut f.block.stmts);
-                            f.block.stmts = b;
-                            Item::Fn(f)
-                        } else {
-                            Item::Fn(f)
-                        }
-                    }
-                    e => e,
-                })
-                .collect();
-            m.content = Some((brace, e));
-            Item::Mod(m)
-        }
+pub use test_env_helpers_macros::*;
 
-        _ => {
-            panic!("The `before_each` macro attribute is only valid when called on a module.")
-        }
-    };
-    TokenStream::from(quote! {#input})
-}
+pub use crate::cleanup::cleanup;
+pub use crate::listener::TestListener;
+pub use crate::logs::{captured_logs, CapturedLogs, Level, LogRecord};
+pub use crate::outcome::TestOutcome;
+pub use crate::repeat::seed;
+pub use crate::summary::ModuleSummary;
+pub use crate::test_info::{current_test, TestInfo};
 
-/// Will skip running the code it is applied on. You can use it to skip tests that aren't working
-/// correctly or that you don't want to run for some reason. There are no checks to make sure it's
-/// applied to a `#[test]` or mod. It will remove whatever it is applied to from the final AST.
-///
-/// ```
-/// #[cfg(test)]
-/// use test_env_helpers::*;
-///
-/// #[cfg(test)]
-/// mod my_tests{
-///     #[skip]
-///     #[test]
-///     fn broken_test(){panic!("I'm hella broke")}
-///     #[skip]
-///     mod broken_mod{
-///         #[test]
-///         fn i_will_not_be_run(){panic!("I get skipped too")}
-///     }
-///     #[test]
-///     fn test_2(){}
-///     #[test]
-///     fn test_3(){}
-/// }
-/// ```
-#[proc_macro_attribute]
-pub fn skip(_metadata: TokenStream, _input: TokenStream) -> TokenStream {
-    TokenStream::from(quote! {})
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::catch_unwind::CatchUnwind;
+    pub use crate::cleanup::run_cleanups;
+    pub use crate::counter::{RegisteredTest, TestCounter};
+    pub use crate::events::{enter_hook, exit_hook};
+    pub use crate::hooks::{Guard, Hooks};
+    pub use crate::junit::SkippedTest;
+    pub use crate::listener::RegisteredListener;
+    pub use crate::outcome::{ShouldPanic, TestReturn};
+    pub use crate::repeat::Repeat;
+    pub use crate::rerun::is_final_run;
+    pub use crate::retry::Retry;
+    pub use crate::semaphore::Permit;
+    pub use crate::tags::run_tagged;
+    pub use crate::test_info::{test_info, Arg, DebugArg, OpaqueArg, TestScope};
+    pub use inventory::submit;
 }
@@ -0,0 +1,115 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::events::Event;
+use crate::outcome::TestOutcome;
+use crate::summary::ModuleSummary;
+use crate::test_info::TestInfo;
+
+/// Observes the tests and hooks of every mod with any of the hooks or tags applied, e.g. to push
+/// metrics or write custom logs. Register a listener by applying [`listener`](attr.listener.html)
+/// to a static holding it; each test binary calls every listener registered in it. All methods do
+/// nothing by default, so a listener only implements the ones it needs.
+///
+/// Listeners are called from the threads the tests run on, so they have to be `Sync`, and are
+/// called for tests running in parallel at the same time.
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::time::Duration;
+/// use test_env_helpers::*;
+///
+/// struct FailureCounter(AtomicUsize);
+///
+/// impl TestListener for FailureCounter {
+///     fn on_test_end(&self, _test: &TestInfo, outcome: &TestOutcome, _duration: Duration) {
+///         if outcome.failed() {
+///             self.0.fetch_add(1, Ordering::SeqCst);
+///         }
+///     }
+/// }
+///
+/// #[listener]
+/// static FAILURES: FailureCounter = FailureCounter(AtomicUsize::new(0));
+/// ```
+#[allow(unused_variables)]
+pub trait TestListener: Sync {
+    /// Called when `test` starts, before any of its hooks.
+    fn on_test_start(&self, test: &TestInfo) {}
+
+    /// Called when `test` has finished with `outcome`, after all of its hooks and cleanups.
+    /// `duration` includes the time spent in its hooks. Retried and repeated tests end once per run.
+    fn on_test_end(&self, test: &TestInfo, outcome: &TestOutcome, duration: Duration) {}
+
+    /// Called instead of [`on_test_end`](Self::on_test_end) when `test` was skipped because its
+    /// tags don't match `TEST_TAGS`.
+    fn on_test_skipped(&self, test: &TestInfo) {}
+
+    /// Called when `hook`, e.g. `before_each`, starts running for `test`.
+    fn on_hook_start(&self, hook: &str, test: &TestInfo) {}
+
+    /// Called when `hook` has finished running for `test`. A hook that panicked ends with the
+    /// outcome of the test it failed.
+    fn on_hook_end(&self, hook: &str, test: &TestInfo, outcome: &TestOutcome, duration: Duration) {}
+
+    /// Called once the last test of the mod at `module_path` has finished and its `after_all` has
+    /// run, which happens as part of that test, before it ends. Only mods with an `after_all` hook
+    /// know when their last test has finished.
+    fn on_module_complete(&self, module_path: &str, summary: &ModuleSummary) {}
+}
+
+/// A listener registered with `#[listener]`, before `main`.
+pub struct RegisteredListener(&'static dyn TestListener);
+
+impl RegisteredListener {
+    pub const fn new(listener: &'static dyn TestListener) -> Self {
+        RegisteredListener(listener)
+    }
+}
+
+inventory::collect!(RegisteredListener);
+
+/// Returns `true` if any listeners are registered.
+pub(crate) fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| {
+        inventory::iter::<RegisteredListener>
+            .into_iter()
+            .next()
+            .is_some()
+    })
+}
+
+/// Calls the method of every registered listener that matches `event`.
+pub(crate) fn notify(event: &Event) {
+    for RegisteredListener(listener) in inventory::iter::<RegisteredListener> {
+        match *event {
+            Event::TestStarted(test) => listener.on_test_start(test),
+            Event::TestFinished {
+                test,
+                outcome: Some(outcome),
+                duration,
+                ..
+            } => listener.on_test_end(test, outcome, duration),
+            Event::TestFinished {
+                test,
+                outcome: None,
+                ..
+            } => listener.on_test_skipped(test),
+            Event::HookStarted {
+                hook,
+                test: Some(test),
+            } => listener.on_hook_start(hook, test),
+            Event::HookFinished {
+                hook,
+                test: Some(test),
+                outcome,
+                duration,
+            } => listener.on_hook_end(hook, test, outcome, duration),
+            Event::HookStarted { test: None, .. } | Event::HookFinished { test: None, .. } => {}
+            Event::ModuleFinished {
+                module_path,
+                summary,
+            } => listener.on_module_complete(module_path, summary),
+        }
+    }
+}
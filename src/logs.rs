@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::test_info::TestInfo;
+
+thread_local! {
+    /// The records logged by the test running on this thread, or `None` outside of a test.
+    static RECORDS: RefCell<Option<Vec<LogRecord>>> = const { RefCell::new(None) };
+}
+
+/// The severity of a [`LogRecord`], shared by `log` and `tracing` records.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Level(u8);
+
+impl Level {
+    pub const ERROR: Level = Level(1);
+    pub const WARN: Level = Level(2);
+    pub const INFO: Level = Level(3);
+    pub const DEBUG: Level = Level(4);
+    pub const TRACE: Level = Level(5);
+
+    fn as_str(self) -> &'static str {
+        match self.0 {
+            1 => "ERROR",
+            2 => "WARN",
+            3 => "INFO",
+            4 => "DEBUG",
+            _ => "TRACE",
+        }
+    }
+}
+
+impl fmt::Debug for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<log::Level> for Level {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => Level::ERROR,
+            log::Level::Warn => Level::WARN,
+            log::Level::Info => Level::INFO,
+            log::Level::Debug => Level::DEBUG,
+            log::Level::Trace => Level::TRACE,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl From<tracing::Level> for Level {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => Level::ERROR,
+            tracing::Level::WARN => Level::WARN,
+            tracing::Level::INFO => Level::INFO,
+            tracing::Level::DEBUG => Level::DEBUG,
+            _ => Level::TRACE,
+        }
+    }
+}
+
+/// A `log` record or `tracing` event captured while a test was running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+impl LogRecord {
+    /// The level the record was logged at.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// The target of the record, which defaults to the module path it was logged from.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The formatted message. The other fields of a `tracing` event follow it as `name=value`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5} {}: {}", self.level, self.target, self.message)
+    }
+}
+
+/// The records the current test has logged so far, returned by [`captured_logs`].
+#[derive(Clone, Debug, Default)]
+pub struct CapturedLogs {
+    records: Vec<LogRecord>,
+}
+
+impl CapturedLogs {
+    /// The records in the order they were logged.
+    pub fn records(&self) -> &[LogRecord] {
+        &self.records
+    }
+
+    /// Returns `true` if a record was logged at exactly `level` with a message containing `text`.
+    pub fn contains(&self, level: Level, text: &str) -> bool {
+        self.records
+            .iter()
+            .any(|record| record.level == level && record.message.contains(text))
+    }
+
+    /// Panics, listing the captured records, unless a record was logged at exactly `level` with a
+    /// message containing `text`.
+    #[track_caller]
+    pub fn assert_contains(&self, level: Level, text: &str) {
+        if !self.contains(level, text) {
+            panic!(
+                "no {} record containing `{}` was logged, the records were:{}",
+                level,
+                text,
+                self.listing()
+            );
+        }
+    }
+
+    /// Panics, listing the captured records, if a record was logged at exactly `level` with a
+    /// message containing `text`.
+    #[track_caller]
+    pub fn assert_not_contains(&self, level: Level, text: &str) {
+        if self.contains(level, text) {
+            panic!(
+                "a {} record containing `{}` was logged, the records were:{}",
+                level,
+                text,
+                self.listing()
+            );
+        }
+    }
+
+    fn listing(&self) -> String {
+        match self.records.as_slice() {
+            [] => " none".to_string(),
+            records => records
+                .iter()
+                .map(|record| format!("\n  {}", record))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the `tracing` events, and with the `log` feature the `log` records, logged on the
+/// current test's thread so far, including those logged by its hooks. Records are captured for
+/// tests with any of the before/after hooks or tags applied, and are printed when the test fails.
+///
+/// Events are captured by a subscriber set as the default of the test's thread while it runs. A
+/// global subscriber the tests installed, e.g. with `#[test_log::test]`, takes precedence and
+/// nothing is captured from it.
+///
+/// `log` records aren't captured by default, since that takes installing a global logger. Enable
+/// the `log` feature, e.g. with `features = ["log"]` on the dependency, to install one the first
+/// time such a test starts, unless the tests installed one before that.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){tracing::info!("connecting")}
+///     #[test]
+///     fn test_1(){
+///         tracing::warn!(attempt = 2, "retrying");
+///         captured_logs().assert_contains(Level::INFO, "connecting");
+///         captured_logs().assert_contains(Level::WARN, "retrying attempt=2");
+///     }
+/// }
+/// ```
+pub fn captured_logs() -> CapturedLogs {
+    RECORDS.with(|records| CapturedLogs {
+        records: records.borrow().clone().unwrap_or_default(),
+    })
+}
+
+/// What a test replaced to capture its records, which [`exit`] puts back.
+pub(crate) struct Capture {
+    previous: Option<Vec<LogRecord>>,
+    #[cfg(feature = "tracing")]
+    _subscriber: Option<tracing::subscriber::DefaultGuard>,
+}
+
+/// Starts capturing the records of a test, until it is passed to [`exit`].
+pub(crate) fn enter() -> Capture {
+    #[cfg(feature = "log")]
+    {
+        use std::sync::Once;
+
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            if log::set_logger(&CaptureLogger).is_ok() {
+                log::set_max_level(log::LevelFilter::Trace);
+            }
+        });
+    }
+    // A subscriber the test runs under already, whether a global one or the one capturing the
+    // events of the test this one is nested in, keeps getting its events.
+    #[cfg(feature = "tracing")]
+    let subscriber = tracing::dispatcher::get_default(|dispatch| {
+        dispatch.is::<tracing::subscriber::NoSubscriber>()
+    })
+    .then(|| tracing::subscriber::set_default(CaptureSubscriber));
+    Capture {
+        previous: RECORDS.with(|records| records.replace(Some(vec![]))),
+        #[cfg(feature = "tracing")]
+        _subscriber: subscriber,
+    }
+}
+
+/// Goes back to capturing the records of the test the one that started `capture` was nested in,
+/// if any, and to the thread's previous `tracing` subscriber.
+pub(crate) fn exit(capture: Capture) {
+    RECORDS.with(|records| *records.borrow_mut() = capture.previous);
+}
+
+/// Prints the records `test` logged, for a test that failed.
+pub(crate) fn print(test: &TestInfo) {
+    let logs = captured_logs();
+    if !logs.records.is_empty() {
+        eprintln!("captured logs of `{}`:{}", test.full_name(), logs.listing());
+    }
+}
+
+/// Returns `true` if the current thread is running a test whose records are captured.
+#[cfg_attr(not(any(feature = "log", feature = "tracing")), allow(dead_code))]
+fn capturing() -> bool {
+    // `try_with`, since records can be logged while the thread's locals are destroyed, and
+    // `try_borrow`, since they can be logged while the records are being read.
+    RECORDS
+        .try_with(|records| matches!(records.try_borrow().as_deref(), Ok(Some(_))))
+        .unwrap_or(false)
+}
+
+#[cfg_attr(not(any(feature = "log", feature = "tracing")), allow(dead_code))]
+fn push(record: LogRecord) {
+    let _ = RECORDS.try_with(|records| {
+        if let Ok(Some(records)) = records.try_borrow_mut().as_deref_mut() {
+            records.push(record);
+        }
+    });
+}
+
+#[cfg(feature = "log")]
+struct CaptureLogger;
+
+#[cfg(feature = "log")]
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        capturing()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if capturing() {
+            push(LogRecord {
+                level: record.level().into(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The default subscriber of a test's thread while it runs, which records its events and ignores
+/// spans.
+#[cfg(feature = "tracing")]
+struct CaptureSubscriber;
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for CaptureSubscriber {
+    fn register_callsite(
+        &self,
+        _: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        // Whether an event is captured depends on the thread it happens on, and other threads may
+        // not have a subscriber at all, so it is decided by `enabled` every time instead of once
+        // per callsite.
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.is_event() && capturing()
+    }
+
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut message = EventMessage::default();
+        event.record(&mut message);
+        let metadata = event.metadata();
+        push(LogRecord {
+            level: (*metadata.level()).into(),
+            target: metadata.target().to_string(),
+            message: (message.message + &message.fields).trim_start().to_string(),
+        });
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+/// Formats the `message` field of an event followed by its other fields.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct EventMessage {
+    message: String,
+    fields: String,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for EventMessage {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name => self.fields.push_str(&format!(" {}={:?}", name, value)),
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message.push_str(&format!("{:?}", value)),
+            name => self.fields.push_str(&format!(" {}={:?}", name, value)),
+        }
+    }
+}
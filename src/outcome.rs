@@ -0,0 +1,110 @@
+use std::any::Any;
+use std::fmt::Debug;
+use std::{panic, thread};
+
+/// Implemented for the return types a test function is allowed to have, so generated code can tell
+/// whether a test that didn't panic still failed by returning an `Err`.
+pub trait TestReturn {
+    /// Returns a description of the failure if this return value represents a failed test.
+    fn failure(&self) -> Option<String>;
+
+    /// The value a test that was skipped at runtime returns.
+    fn skipped() -> Self;
+}
+
+impl TestReturn for () {
+    fn failure(&self) -> Option<String> {
+        None
+    }
+
+    fn skipped() -> Self {}
+}
+
+impl<T: Default, E: Debug> TestReturn for Result<T, E> {
+    fn failure(&self) -> Option<String> {
+        self.as_ref().err().map(|err| format!("{:?}", err))
+    }
+
+    fn skipped() -> Self {
+        Ok(T::default())
+    }
+}
+
+/// Whether a test is expected to panic, as declared by its `#[should_panic]` attribute.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShouldPanic {
+    No,
+    Yes,
+    /// The panic message has to contain this text, like with `#[should_panic(expected = "..")]`.
+    YesWithMessage(&'static str),
+}
+
+/// How a test ended, for `after_each` hooks that take a `&TestOutcome` parameter. Unlike other
+/// `after_each` hooks, those also run when the test panics, so they can e.g. keep the temp dir of
+/// a failed test around.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    /// The test panicked with this message, and wasn't expected to, or not with this message.
+    /// Panics with a payload other than a string have the message `Box<dyn Any>`, like the standard
+    /// panic hook prints.
+    Panicked(String),
+    /// The test returned an `Err`, formatted with `Debug`.
+    Failed(String),
+}
+
+impl TestOutcome {
+    /// The outcome of a test that ended with `result`. Like the test harness, a test that is
+    /// expected to panic passes if it panicked with the expected message and fails if it didn't
+    /// panic at all.
+    #[doc(hidden)]
+    pub fn of<T: TestReturn>(result: &thread::Result<T>, should_panic: ShouldPanic) -> Self {
+        match (result, should_panic) {
+            (Ok(value), ShouldPanic::No) => match value.failure() {
+                None => TestOutcome::Passed,
+                Some(failure) => TestOutcome::Failed(failure),
+            },
+            (Ok(_), _) => TestOutcome::Failed("test did not panic as expected".to_string()),
+            (Err(payload), should_panic) => {
+                let message = panic_message(&**payload);
+                match should_panic {
+                    ShouldPanic::Yes => TestOutcome::Passed,
+                    ShouldPanic::YesWithMessage(expected) if message.contains(expected) => {
+                        TestOutcome::Passed
+                    }
+                    _ => TestOutcome::Panicked(message),
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the test passed.
+    pub fn passed(&self) -> bool {
+        matches!(self, TestOutcome::Passed)
+    }
+
+    /// Returns `true` if the test panicked or returned an `Err`.
+    pub fn failed(&self) -> bool {
+        !self.passed()
+    }
+}
+
+/// Returns the message of a caught panic.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Returns the value of a test run that didn't panic, or resumes its panic.
+pub(crate) fn value_or_resume<T>(result: thread::Result<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => panic::resume_unwind(err),
+    }
+}
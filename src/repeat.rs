@@ -0,0 +1,113 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+
+use crate::outcome::{value_or_resume, ShouldPanic, TestOutcome, TestReturn};
+use crate::rerun::set_repeat_pending;
+
+/// Number of times to run every test in a hooked module that doesn't have its own `repeat` count.
+const REPEAT_ENV: &str = "TEST_ENV_HELPERS_REPEAT";
+/// Seed to use for the first iteration instead of a random one.
+const SEED_ENV: &str = "TEST_ENV_HELPERS_SEED";
+
+thread_local! {
+    static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+    static SEED_USED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn base_seed() -> u64 {
+    static BASE_SEED: OnceLock<u64> = OnceLock::new();
+    *BASE_SEED.get_or_init(|| {
+        env::var(SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| RandomState::new().build_hasher().finish())
+    })
+}
+
+/// Returns a seed for tests that generate random data. Each iteration of a `repeat`ed test gets a
+/// different seed, and the seed is printed if that iteration fails. Set the `TEST_ENV_HELPERS_SEED`
+/// environment variable to the printed value to make the first iteration use it again.
+///
+/// ```
+/// let seed = test_env_helpers::seed();
+/// assert_eq!(seed, test_env_helpers::seed());
+/// ```
+pub fn seed() -> u64 {
+    SEED_USED.with(|used| used.set(true));
+    SEED.with(Cell::get).unwrap_or_else(base_seed)
+}
+
+/// Drives the iterations of a test annotated with `#[repeat(n)]`, or of any test in a hooked module
+/// when `TEST_ENV_HELPERS_REPEAT` is set. Used by the generated code the same way as `Retry`.
+pub struct Repeat {
+    name: &'static str,
+    iteration: usize,
+    iterations: usize,
+    should_panic: ShouldPanic,
+    previous: bool,
+    previous_seed: Option<u64>,
+}
+
+impl Repeat {
+    pub fn new(name: &'static str, iterations: usize, should_panic: ShouldPanic) -> Self {
+        Repeat {
+            name,
+            iteration: 0,
+            iterations: iterations.max(1),
+            should_panic,
+            previous: false,
+            previous_seed: None,
+        }
+    }
+
+    pub fn from_env(name: &'static str, should_panic: ShouldPanic) -> Self {
+        let iterations = env::var(REPEAT_ENV)
+            .ok()
+            .and_then(|iterations| iterations.parse().ok())
+            .unwrap_or(1);
+        Repeat::new(name, iterations, should_panic)
+    }
+
+    pub fn begin(&mut self) {
+        self.iteration += 1;
+        self.previous = set_repeat_pending(self.iteration < self.iterations);
+        let seed = base_seed().wrapping_add(self.iteration as u64 - 1);
+        self.previous_seed = SEED.with(|cell| cell.replace(Some(seed)));
+        SEED_USED.with(|used| used.set(false));
+    }
+
+    /// Returns the value the test should return, or `None` if the test should be run again. Resumes
+    /// the panic of the iteration that failed, or of the last one if the test is expected to panic.
+    pub fn end<T: TestReturn>(&mut self, result: std::thread::Result<T>) -> Option<T> {
+        set_repeat_pending(self.previous);
+        let seed = SEED.with(|cell| cell.replace(self.previous_seed));
+        let failure = match TestOutcome::of(&result, self.should_panic) {
+            TestOutcome::Passed => None,
+            TestOutcome::Failed(failure) => Some(failure),
+            TestOutcome::Panicked(_) => Some("a panic".to_string()),
+        };
+        match failure {
+            Some(failure) => {
+                // A test run only once, like every test in a hooked mod, fails as it would anyway.
+                if self.iterations > 1 {
+                    eprintln!(
+                        "`{}` failed on iteration {}/{} with {}",
+                        self.name, self.iteration, self.iterations, failure
+                    );
+                }
+                if let Some(seed) = seed.filter(|_| SEED_USED.with(Cell::get)) {
+                    eprintln!(
+                        "seed: {}, rerun with {}={} to reproduce",
+                        seed, SEED_ENV, seed
+                    );
+                }
+            }
+            None if self.iteration < self.iterations => return None,
+            None => {}
+        }
+        Some(value_or_resume(result))
+    }
+}
@@ -0,0 +1,28 @@
+use std::cell::Cell;
+
+thread_local! {
+    static RETRY_PENDING: Cell<bool> = const { Cell::new(false) };
+    static REPEAT_PENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns `true` if the run of the test that just finished on this thread is its last one, i.e. it
+/// won't be retried because it failed or repeated because it passed. `after_all` uses this so a
+/// test is only counted as finished once, no matter how many times `retry` or `repeat` run its
+/// body.
+pub fn is_final_run(passed: bool) -> bool {
+    if passed {
+        !REPEAT_PENDING.with(Cell::get)
+    } else {
+        !RETRY_PENDING.with(Cell::get)
+    }
+}
+
+/// Records whether a failure of the current attempt will be retried, returning the previous value.
+pub(crate) fn set_retry_pending(pending: bool) -> bool {
+    RETRY_PENDING.with(|cell| cell.replace(pending))
+}
+
+/// Records whether a pass of the current iteration will be repeated, returning the previous value.
+pub(crate) fn set_repeat_pending(pending: bool) -> bool {
+    REPEAT_PENDING.with(|cell| cell.replace(pending))
+}
@@ -0,0 +1,80 @@
+use std::env;
+
+use crate::outcome::{value_or_resume, ShouldPanic, TestOutcome, TestReturn};
+use crate::rerun::set_retry_pending;
+
+/// Setting this environment variable to anything other than `0` disables all retries.
+const NO_RETRY_ENV: &str = "TEST_ENV_HELPERS_NO_RETRY";
+
+fn retries_disabled() -> bool {
+    env::var(NO_RETRY_ENV)
+        .map(|value| !value.is_empty() && value != "0")
+        .unwrap_or(false)
+}
+
+/// Drives the attempts of a test annotated with `#[retry(n)]`. The generated code calls
+/// [`Retry::begin`] before every attempt and hands the caught result of that attempt to
+/// [`Retry::end`], which decides whether to stop or go around again.
+pub struct Retry {
+    name: &'static str,
+    attempt: usize,
+    attempts: usize,
+    should_panic: ShouldPanic,
+    previous: bool,
+}
+
+impl Retry {
+    pub fn new(name: &'static str, attempts: usize, should_panic: ShouldPanic) -> Self {
+        let attempts = if retries_disabled() {
+            1
+        } else {
+            attempts.max(1)
+        };
+        Retry {
+            name,
+            attempt: 0,
+            attempts,
+            should_panic,
+            previous: false,
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.attempt += 1;
+        self.previous = set_retry_pending(self.attempt < self.attempts);
+    }
+
+    /// Returns the value the test should return, or `None` if the test should be run again. Resumes
+    /// the panic of the final attempt if every attempt panicked, or of the attempt that panicked as
+    /// the test expected.
+    pub fn end<T: TestReturn>(&mut self, result: std::thread::Result<T>) -> Option<T> {
+        set_retry_pending(self.previous);
+        let last = self.attempt >= self.attempts;
+        match TestOutcome::of(&result, self.should_panic) {
+            TestOutcome::Passed => {
+                if self.attempt > 1 {
+                    eprintln!(
+                        "`{}` passed on attempt {}/{}",
+                        self.name, self.attempt, self.attempts
+                    );
+                }
+                Some(value_or_resume(result))
+            }
+            _ if last => Some(value_or_resume(result)),
+            TestOutcome::Failed(failure) => {
+                eprintln!(
+                    "`{}` failed on attempt {}/{} with {}, retrying",
+                    self.name, self.attempt, self.attempts, failure
+                );
+                None
+            }
+            TestOutcome::Panicked(_) => {
+                eprintln!(
+                    "`{}` panicked on attempt {}/{}, retrying",
+                    self.name, self.attempt, self.attempts
+                );
+                None
+            }
+        }
+    }
+}
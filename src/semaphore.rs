@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Waiting longer than this for a slot prints how long the test waited.
+const LONG_WAIT: Duration = Duration::from_secs(1);
+
+/// A counting semaphore whose permits are given back when they are dropped, even if the test
+/// holding one panicked. Unlike a `Mutex` it is never poisoned, so one failing test doesn't make
+/// every later test fail too.
+struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Semaphore {
+            available: Mutex::new(permits.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    fn available(&self) -> MutexGuard<'_, usize> {
+        self.available
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn acquire(&self) {
+        let mut available = self.available();
+        while *available == 0 {
+            available = self
+                .released
+                .wait(available)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *available -= 1;
+    }
+
+    fn release(&self) {
+        *self.available() += 1;
+        self.released.notify_one();
+    }
+}
+
+/// Held by a `#[serial]` or `#[max_concurrency]` test for its whole run, including hooks. Lets the
+/// next waiting test run when dropped.
+pub struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Permit {
+    /// Waits until fewer than `permits` other tests sharing `key` are running. Every use of a key
+    /// shares the limit of the first one to run.
+    pub fn acquire(name: &'static str, key: &'static str, permits: usize) -> Self {
+        static SEMAPHORES: OnceLock<Mutex<HashMap<&'static str, Arc<Semaphore>>>> = OnceLock::new();
+        let semaphore = SEMAPHORES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key)
+            .or_insert_with(|| Arc::new(Semaphore::new(permits)))
+            .clone();
+        let start = Instant::now();
+        semaphore.acquire();
+        let waited = start.elapsed();
+        if waited >= LONG_WAIT {
+            eprintln!("`{}` waited {:.2?} for a slot in `{}`", name, waited, key);
+        }
+        Permit { semaphore }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::time::Duration;
+
+/// How the tests of a mod went, for `after_all` hooks that take a `&ModuleSummary` parameter, e.g.
+/// to keep a shared database dump around only when something failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleSummary {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    duration: Duration,
+}
+
+impl ModuleSummary {
+    pub(crate) fn new(passed: usize, failed: usize, skipped: usize, duration: Duration) -> Self {
+        ModuleSummary {
+            passed,
+            failed,
+            skipped,
+            duration,
+        }
+    }
+
+    /// The number of tests that ran, which is every test of the mod that `TEST_TAGS` selected.
+    pub fn executed(&self) -> usize {
+        self.passed + self.failed
+    }
+
+    /// The number of tests that passed, after any retries.
+    pub fn passed(&self) -> usize {
+        self.passed
+    }
+
+    /// The number of tests that panicked or returned an `Err`, after any retries.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// The number of tests that didn't run because `TEST_TAGS` didn't select them or they are
+    /// `#[ignore]`d.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// The time from the first test of the mod starting to the last one finishing. Tests run in
+    /// parallel, so this is usually less than the time each of them took added up.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl fmt::Display for ModuleSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped in {:.2}s",
+            self.passed,
+            self.failed,
+            self.skipped,
+            self.duration.as_secs_f64()
+        )
+    }
+}
@@ -0,0 +1,57 @@
+use std::env;
+use std::sync::OnceLock;
+
+/// Comma separated list of tags to select tests by. A tag prefixed with `!` excludes tests with
+/// that tag, e.g. `TEST_TAGS=db,!slow` runs the tests tagged `db` that aren't also tagged `slow`.
+const TAGS_ENV: &str = "TEST_TAGS";
+
+#[derive(Default)]
+struct Selection {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+fn selection() -> &'static Selection {
+    static SELECTION: OnceLock<Selection> = OnceLock::new();
+    SELECTION.get_or_init(|| {
+        let mut selection = Selection::default();
+        for tag in env::var(TAGS_ENV).unwrap_or_default().split(',') {
+            let tag = tag.trim();
+            match tag.strip_prefix('!') {
+                Some(tag) => selection.exclude.push(tag.trim().to_string()),
+                None if !tag.is_empty() => selection.include.push(tag.to_string()),
+                None => {}
+            }
+        }
+        selection
+    })
+}
+
+/// Returns `true` if a test with `tags` should run according to `TEST_TAGS`. When `TEST_TAGS` lists
+/// any tags to include, tests without any of them are skipped, including tests without tags.
+pub fn tags_selected(tags: &[&str]) -> bool {
+    let selection = selection();
+    let included = selection.include.is_empty()
+        || selection
+            .include
+            .iter()
+            .any(|tag| tags.contains(&tag.as_str()));
+    included
+        && !selection
+            .exclude
+            .iter()
+            .any(|tag| tags.contains(&tag.as_str()))
+}
+
+/// Like [`tags_selected`], but prints that the test named `name` is being skipped if it isn't
+/// selected.
+pub fn run_tagged(name: &str, tags: &[&str]) -> bool {
+    let selected = tags_selected(tags);
+    if !selected {
+        eprintln!(
+            "skipping `{}`, its tags {:?} don't match {}",
+            name, tags, TAGS_ENV
+        );
+    }
+    selected
+}
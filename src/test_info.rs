@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::panic;
+use std::thread;
+use std::time::Instant;
+
+use crate::cleanup::{self, Cleanup};
+use crate::events::{self, Event};
+use crate::logs::{self, Capture};
+use crate::outcome::{ShouldPanic, TestOutcome, TestReturn};
+
+thread_local! {
+    static CURRENT_TEST: RefCell<Option<TestInfo>> = const { RefCell::new(None) };
+}
+
+/// Describes a running test, e.g. to name temp dirs, log files or database schemas after it. Hooks
+/// get it by taking a `&TestInfo` parameter, and anything else running on the test's thread can get
+/// it from [`current_test`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestInfo {
+    name: &'static str,
+    module_path: &'static str,
+    tags: &'static [&'static str],
+    args: Vec<String>,
+    should_panic: ShouldPanic,
+}
+
+impl TestInfo {
+    #[doc(hidden)]
+    pub fn new(
+        name: &'static str,
+        module_path: &'static str,
+        tags: &'static [&'static str],
+        args: Vec<String>,
+        should_panic: ShouldPanic,
+    ) -> Self {
+        TestInfo {
+            name,
+            module_path,
+            tags,
+            args,
+            should_panic,
+        }
+    }
+
+    /// The name of the test function, e.g. `test_1`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The path of the module the test is in, like [`module_path!`] would return there.
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+
+    /// The name of the test including its module path, e.g. `my_crate::my_tests::test_1`.
+    pub fn full_name(&self) -> String {
+        format!("{}::{}", self.module_path, self.name)
+    }
+
+    /// The tags of the test, sorted by name.
+    pub fn tags(&self) -> &'static [&'static str] {
+        self.tags
+    }
+
+    /// The arguments of the test, like the values of a `#[test_case]`, formatted with `Debug`.
+    /// Arguments whose type doesn't implement `Debug` are shown as `_`.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Returns `true` if the test has a `#[should_panic]` attribute, so it passes by panicking.
+    pub fn should_panic(&self) -> bool {
+        self.should_panic != ShouldPanic::No
+    }
+}
+
+/// Returns the test running on the current thread, if it has any of the before/after hooks or
+/// tags applied. Threads spawned by the test don't have a current test.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[tag(db)]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn schema_name() -> String {
+///         current_test().unwrap().name().to_string()
+///     }
+///     #[test]
+///     fn test_1(){assert_eq!(schema_name(), "test_1")}
+/// }
+/// ```
+pub fn current_test() -> Option<TestInfo> {
+    CURRENT_TEST.with(|current| current.borrow().clone())
+}
+
+/// The info of the current test for hooks taking a `&TestInfo`, which only run inside a test.
+pub fn test_info() -> TestInfo {
+    current_test().expect("hooks only run inside a test")
+}
+
+/// Makes a test the [`current_test`] of its thread until dropped, capturing what it logs, and runs
+/// the cleanups the test registered that haven't run yet when dropped.
+pub struct TestScope {
+    previous: Option<TestInfo>,
+    previous_cleanups: Option<Vec<Cleanup>>,
+    logs: Option<Capture>,
+    started: Instant,
+}
+
+impl TestScope {
+    pub fn enter(info: TestInfo) -> Self {
+        events::emit(Event::TestStarted(&info));
+        events::take_hooks_time();
+        let previous = CURRENT_TEST.with(|current| current.replace(Some(info)));
+        TestScope {
+            previous,
+            previous_cleanups: cleanup::enter(),
+            logs: Some(logs::enter()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Reports that the test was skipped at runtime.
+    pub fn skip(&self) {
+        if events::enabled() {
+            events::emit(Event::TestFinished {
+                test: &test_info(),
+                outcome: None,
+                duration: self.started.elapsed(),
+                in_hooks: events::take_hooks_time(),
+                failed_hook: None,
+            });
+        }
+    }
+
+    /// Runs the remaining cleanups of a test that ended with `result`, reports how it ended and
+    /// prints its captured logs if it failed, counting a panic the test expected as a pass.
+    /// Returns the result, failed by a cleanup if one panicked. A panic in one of the hooks of the
+    /// test is raised again with the name and location of the hook, so it isn't mistaken for a
+    /// failure of the test itself.
+    pub fn finish<T: TestReturn>(&self, result: thread::Result<T>) -> thread::Result<T> {
+        // An expected panic counts as a failure here, so a panicking cleanup doesn't replace it.
+        let result = match cleanup::run_cleanups(TestOutcome::of(&result, ShouldPanic::No).failed())
+        {
+            Some(err) => Err(err),
+            None => result,
+        };
+        let test = test_info();
+        let outcome = TestOutcome::of(&result, test.should_panic);
+        let failed_hook = events::finish_hook(&test, &outcome);
+        if outcome.failed() {
+            logs::print(&test);
+        }
+        events::emit(Event::TestFinished {
+            test: &test,
+            outcome: Some(&outcome),
+            duration: self.started.elapsed(),
+            in_hooks: events::take_hooks_time(),
+            failed_hook: failed_hook.map(|(hook, _)| hook),
+        });
+        match (failed_hook, outcome) {
+            (Some((hook, location)), TestOutcome::Panicked(message)) => panic!(
+                "{} hook failed for `{}` (hook at {}): {}",
+                hook,
+                test.full_name(),
+                location,
+                message
+            ),
+            _ => result,
+        }
+    }
+}
+
+impl Drop for TestScope {
+    fn drop(&mut self) {
+        let err = cleanup::exit(self.previous_cleanups.take());
+        if let Some(capture) = self.logs.take() {
+            logs::exit(capture);
+        }
+        let previous = self.previous.take();
+        CURRENT_TEST.with(|current| *current.borrow_mut() = previous);
+        if let Some(err) = err {
+            panic::resume_unwind(err);
+        }
+    }
+}
+
+/// Wraps a test argument so it can be described whether or not its type implements `Debug`. The
+/// generated code calls `(&&Arg(&value)).describe()` with both [`DebugArg`] and [`OpaqueArg`] in
+/// scope, and method resolution picks `DebugArg` when it applies.
+pub struct Arg<'a, T>(pub &'a T);
+
+pub trait DebugArg {
+    fn describe(&self) -> String;
+}
+
+impl<T: Debug> DebugArg for &Arg<'_, T> {
+    fn describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+pub trait OpaqueArg {
+    fn describe(&self) -> String;
+}
+
+impl<T> OpaqueArg for Arg<'_, T> {
+    fn describe(&self) -> String {
+        "_".to_string()
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::events::{self, Event};
+
+/// Directory to write a report of the slowest tests and hooks of each mod to for each test binary,
+/// e.g. `TEST_ENV_HELPERS_TIMINGS=target/timings`.
+const TIMINGS_ENV: &str = "TEST_ENV_HELPERS_TIMINGS";
+
+/// Number of milliseconds a test may take before a warning is printed, e.g.
+/// `TEST_ENV_HELPERS_SLOW_MS=500`.
+const SLOW_ENV: &str = "TEST_ENV_HELPERS_SLOW_MS";
+
+/// How many of the slowest tests of each mod the report lists.
+const SLOWEST: usize = 10;
+
+#[derive(Default)]
+struct ModuleTimings {
+    /// The name of each test with how long it took and how much of that was spent in its hooks.
+    tests: Vec<(String, Duration, Duration)>,
+    /// How often each hook ran, how long it took in total, and its slowest run.
+    hooks: BTreeMap<&'static str, (usize, Duration, Duration)>,
+}
+
+struct Report {
+    path: PathBuf,
+    name: String,
+    modules: BTreeMap<&'static str, ModuleTimings>,
+}
+
+fn report() -> Option<&'static Mutex<Report>> {
+    static REPORT: OnceLock<Option<Mutex<Report>>> = OnceLock::new();
+    REPORT
+        .get_or_init(|| {
+            let dir = PathBuf::from(env::var_os(TIMINGS_ENV).filter(|dir| !dir.is_empty())?);
+            if let Err(err) = fs::create_dir_all(&dir) {
+                eprintln!("can't create {} `{}`: {}", TIMINGS_ENV, dir.display(), err);
+                return None;
+            }
+            let name = events::binary_name();
+            events::at_exit(write);
+            Some(Mutex::new(Report {
+                path: dir.join(format!("{}.txt", name)),
+                name,
+                modules: BTreeMap::new(),
+            }))
+        })
+        .as_ref()
+}
+
+fn slow_threshold() -> Option<Duration> {
+    static THRESHOLD: OnceLock<Option<Duration>> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| {
+        let value = env::var(SLOW_ENV).ok()?;
+        match value.trim().parse() {
+            Ok(millis) => Some(Duration::from_millis(millis)),
+            Err(_) => {
+                eprintln!(
+                    "{} should be a number of milliseconds, but it is `{}`",
+                    SLOW_ENV, value
+                );
+                None
+            }
+        }
+    })
+}
+
+/// Returns `true` if tests and hooks are timed.
+pub(crate) fn enabled() -> bool {
+    report().is_some() || slow_threshold().is_some()
+}
+
+/// Warns about the test that finished in `event` if it was slow, and adds the test or hook that
+/// finished to the report, which is written when the test binary exits. Only the last run of a
+/// retried or repeated test is kept.
+pub(crate) fn record(event: &Event) {
+    match event {
+        Event::TestFinished {
+            test,
+            outcome: Some(_),
+            duration,
+            in_hooks,
+            ..
+        } => {
+            if let Some(threshold) = slow_threshold().filter(|threshold| duration > threshold) {
+                // Written to stderr directly, since `eprintln!` would be captured along with the
+                // output of the test.
+                let _ = writeln!(
+                    io::stderr(),
+                    "slow test `{}` took {:.3}s, {:.3}s of it in hooks, over the {}ms of {}",
+                    test.full_name(),
+                    duration.as_secs_f64(),
+                    in_hooks.as_secs_f64(),
+                    threshold.as_millis(),
+                    SLOW_ENV
+                );
+            }
+            if let Some(report) = report() {
+                let mut report = report.lock().unwrap_or_else(|err| err.into_inner());
+                let timing = (events::case_name(test), *duration, *in_hooks);
+                let tests = &mut report.modules.entry(test.module_path()).or_default().tests;
+                events::keep_last_run(tests, timing, |(name, ..)| name);
+            }
+        }
+        Event::HookFinished {
+            hook,
+            test: Some(test),
+            duration,
+            ..
+        } => {
+            if let Some(report) = report() {
+                let mut report = report.lock().unwrap_or_else(|err| err.into_inner());
+                let module = report.modules.entry(test.module_path()).or_default();
+                let (runs, total, slowest) = module.hooks.entry(hook).or_default();
+                *runs += 1;
+                *total += *duration;
+                *slowest = (*slowest).max(*duration);
+            }
+        }
+        _ => {}
+    }
+}
+
+extern "C" fn write() {
+    if let Some(report) = report() {
+        let report = report.lock().unwrap_or_else(|err| err.into_inner());
+        events::write_report(&report.path, &report.render());
+    }
+}
+
+impl ModuleTimings {
+    fn total(&self) -> Duration {
+        self.tests.iter().map(|(_, duration, _)| *duration).sum()
+    }
+}
+
+impl Report {
+    fn render(&self) -> String {
+        let mut text = format!("Timings of the tests in `{}`, slowest first\n", self.name);
+        let mut modules: Vec<_> = self.modules.iter().collect();
+        modules.sort_by_key(|(_, module)| std::cmp::Reverse(module.total()));
+        for (module_path, module) in modules {
+            let in_hooks: Duration = module.tests.iter().map(|(_, _, in_hooks)| *in_hooks).sum();
+            let _ = writeln!(
+                text,
+                "\n{}: {} tests took {:.3}s, {:.3}s of it in hooks",
+                module_path,
+                module.tests.len(),
+                module.total().as_secs_f64(),
+                in_hooks.as_secs_f64()
+            );
+            let mut hooks: Vec<_> = module.hooks.iter().collect();
+            hooks.sort_by_key(|(_, (_, total, _))| std::cmp::Reverse(*total));
+            if !hooks.is_empty() {
+                text.push_str("  hooks:\n");
+            }
+            for (hook, (runs, total, slowest)) in hooks {
+                let _ = writeln!(
+                    text,
+                    "    {:<12} {:>9.3}s, {}x, slowest {:.3}s",
+                    hook,
+                    total.as_secs_f64(),
+                    runs,
+                    slowest.as_secs_f64()
+                );
+            }
+            let mut tests: Vec<_> = module.tests.iter().collect();
+            tests.sort_by_key(|(_, duration, _)| std::cmp::Reverse(*duration));
+            text.push_str("  tests:\n");
+            for (name, duration, in_hooks) in tests.iter().take(SLOWEST) {
+                let _ = writeln!(
+                    text,
+                    "    {:>9.3}s {} ({:.3}s in hooks)",
+                    duration.as_secs_f64(),
+                    name,
+                    in_hooks.as_secs_f64()
+                );
+            }
+            if tests.len() > SLOWEST {
+                let _ = writeln!(text, "    and {} faster tests", tests.len() - SLOWEST);
+            }
+        }
+        text
+    }
+}
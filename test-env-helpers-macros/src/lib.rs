@@ -0,0 +1,1366 @@
+//! Procedural macros for [test-env-helpers](https://docs.rs/test-env-helpers). This crate is not
+//! meant to be used directly; depend on `test-env-helpers` instead, which re-exports everything
+//! here along with the runtime support the generated code relies on.
+
+// The examples are wrapped in `#[cfg(test)]` like real test modules would be.
+#![allow(clippy::test_attr_in_doctest)]
+
+extern crate proc_macro;
+mod test_fn;
+mod utils;
+
+use crate::test_fn::is_test;
+use crate::utils::{
+    after_all_block, after_all_statics, after_each_stmts, append_stmts, apply_test_hooks,
+    before_all_once, before_each_stmts, called_hook_fn, counted_test, expansion, hook_applies,
+    hook_sig, hook_warnings, hooked_block, hooked_fn, is_attr, limit_args, mark_hooked_macros,
+    mark_hooked_test, mark_tests, module_items, outcome_block, reject_lifecycle_conflict,
+    rerun_block, should_panic, skipped_tests, tag_names, take_hook, take_optional_hook,
+    take_test_attrs, takes_outcome, test_name, test_scope, test_tags, unsupported_item, warning,
+    with_hooks_block, HookArgs, HookedArg, HOOKS,
+};
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span};
+use quote::{format_ident, quote};
+use syn::parse_macro_input;
+use syn::parse_quote;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::AttributeArgs;
+use syn::Block;
+use syn::Item;
+use syn::LitInt;
+use syn::Path;
+use syn::Token;
+
+/// Splits the `test_attrs = [..]` argument off the arguments of a module attribute, returning the
+/// compile error from the macro if it is malformed, like `parse_macro_input!` does.
+macro_rules! split_test_attrs {
+    ($metadata:expr) => {
+        match take_test_attrs($metadata.into()) {
+            Ok((metadata, test_attrs)) => (TokenStream::from(metadata), test_attrs),
+            Err(err) => return TokenStream::from(err.to_compile_error()),
+        }
+    };
+}
+
+/// Will run the given functions at the end of a single test, after the test itself. Useful for
+/// teardown that only some of the tests in a mod need. Like `after_each`, the functions aren't run
+/// if the test panics. They run before any `after_each` code of the mod, and `after_all` still
+/// counts the test as finished if they panic.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn drop_users(){println!("I only get run after `test_1`")}
+///     #[after(drop_users)]
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn after(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    test_hook("after", metadata, input)
+}
+
+/// Will run the code in the matching `after_all` function exactly once when all of the tests have
+/// run. This works by counting the number of `#[test]` attributes and decrementing a counter at
+/// the beginning of every test. Once the counter reaches 0, it will run the code in `after_all`.
+/// It uses [std::sync::Once](https://doc.rust-lang.org/std/sync/struct.Once.html) internally
+/// to ensure that the code is run at maximum one time. The function can take a `&ModuleSummary`
+/// parameter with how many of the tests passed, failed and were skipped, and how long they took.
+///
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[after_all]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn after_all(){println!("I only get run once at the very end")}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+///
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[after_all]
+/// #[cfg(test)]
+/// mod my_tests{
+///     use test_env_helpers::ModuleSummary;
+///     fn after_all(summary: &ModuleSummary){
+///         println!("my_tests: {}", summary);
+///         if summary.failed() > 0 {println!("I can keep the database dump around")}
+///     }
+///     #[test]
+///     fn test_1(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn after_all(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(metadata as HookArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            reject_lifecycle_conflict(&m, &["lifecycle"])?;
+            let (brace, items) = module_items(&mut m, "after_all")?;
+            let sig = hook_sig(&items, "after_all");
+            let (after_all_fn_block, everything_else) = take_hook(&m, items, "after_all")?;
+            let matched = everything_else
+                .iter()
+                .filter(
+                    |t| matches!(t, Item::Fn(f) if hook_applies(f, "after_all", &args, &m.attrs)),
+                )
+                .count();
+            let mut tests = vec![];
+
+            let mut e: Vec<Item> = everything_else
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if hook_applies(&f, "after_all", &args, &m.attrs) {
+                            tests.push(counted_test(&f, &args, &m.attrs));
+                            mark_hooked_test(&mut f);
+                            f.block = Box::new(after_all_block(&f, &f.block, &after_all_fn_block));
+                            Item::Fn(f)
+                        } else {
+                            Item::Fn(f)
+                        }
+                    }
+                    el => el,
+                })
+                .collect();
+
+            let mut once_content = after_all_statics(&tests);
+            let hooked = mark_hooked_macros(&mut e, "after_all", &args);
+            if hooked {
+                once_content.push(hooked_fn("after_all", &after_all_fn_block, &sig));
+            }
+            once_content.append(&mut e);
+
+            let matched = matched + usize::from(hooked);
+            let mut warnings = hook_warnings(&m, &once_content, "after_all", matched);
+            once_content.append(&mut warnings);
+            m.content = Some((brace, once_content));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "after_all", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will run the code in the matching `after_each` function at the end of every `#[test]` function.
+/// Useful if you want to cleanup after a test or reset some external state. If the test panics,
+/// this code will not be run, unless the function takes a `&TestOutcome` parameter. It is then run
+/// after every test with how the test ended, so it can e.g. keep the logs of failed tests around.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[after_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn after_each(){println!("I get run at the very end of each function")}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+///
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[after_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     use test_env_helpers::TestOutcome;
+///     fn after_each(outcome: &TestOutcome){
+///         if outcome.failed(){println!("I get run after failed tests too")}
+///     }
+///     #[test]
+///     fn test_1(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn after_each(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(metadata as HookArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            reject_lifecycle_conflict(&m, &["lifecycle"])?;
+            let (brace, items) = module_items(&mut m, "after_each")?;
+            let sig = hook_sig(&items, "after_each");
+            let (after_each_fn_block, everything_else) = take_hook(&m, items, "after_each")?;
+            let matched = everything_else
+                .iter()
+                .filter(
+                    |t| matches!(t, Item::Fn(f) if hook_applies(f, "after_each", &args, &m.attrs)),
+                )
+                .count();
+
+            let mut e: Vec<Item> = everything_else
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if hook_applies(&f, "after_each", &args, &m.attrs) {
+                            mark_hooked_test(&mut f);
+                            if takes_outcome(&sig) {
+                                f.block =
+                                    Box::new(outcome_block(&f, &f.block, &after_each_fn_block));
+                            } else {
+                                append_stmts(&mut f.block, &after_each_stmts(&after_each_fn_block));
+                            }
+                            Item::Fn(f)
+                        } else {
+                            Item::Fn(f)
+                        }
+                    }
+                    e => e,
+                })
+                .collect();
+            let hooked = mark_hooked_macros(&mut e, "after_each", &args);
+            if hooked {
+                e.push(hooked_fn("after_each", &after_each_fn_block, &sig));
+            }
+            let matched = matched + usize::from(hooked);
+            let mut warnings = hook_warnings(&m, &e, "after_each", matched);
+            e.append(&mut warnings);
+            m.content = Some((brace, e));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "after_each", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will run the given functions at the start of a single test, before the test itself. Useful for
+/// setup that only some of the tests in a mod need. The functions run after any `before_all` and
+/// `before_each` code of the mod, no matter in which order the attributes are declared, and
+/// `after_all` still counts the test as finished if they panic.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I get run before every test")}
+///     fn seed_users(){println!("I only get run before `test_1`, after `before_each`")}
+///     #[before(seed_users)]
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn before(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    test_hook("before", metadata, input)
+}
+
+/// Will run the code in the matching `before_all` function exactly once at the very beginning of a
+/// test run. It uses [std::sync::Once](https://doc.rust-lang.org/std/sync/struct.Once.html) internally
+/// to ensure that the code is run at maximum one time. Useful for setting up some external state
+/// that will be reused in multiple tests.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_all]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_all(){println!("I get run at the very beginning of the test suite")}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn before_all(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(metadata as HookArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            reject_lifecycle_conflict(&m, &["lifecycle"])?;
+            let (brace, items) = module_items(&mut m, "before_all")?;
+            let sig = hook_sig(&items, "before_all");
+            let (before_all_fn_block, everything_else) = take_hook(&m, items, "before_all")?;
+            let matched = everything_else
+                .iter()
+                .filter(
+                    |t| matches!(t, Item::Fn(f) if hook_applies(f, "before_all", &args, &m.attrs)),
+                )
+                .count();
+            let (q, static_once) = before_all_once(&before_all_fn_block);
+
+            let mut e: Vec<Item> = everything_else
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if hook_applies(&f, "before_all", &args, &m.attrs) {
+                            mark_hooked_test(&mut f);
+                            let mut stmts = vec![q.clone()];
+                            stmts.append(&mut f.block.stmts);
+                            f.block.stmts = stmts;
+                            Item::Fn(f)
+                        } else {
+                            Item::Fn(f)
+                        }
+                    }
+                    e => e,
+                })
+                .collect();
+
+            let mut once_content = vec![static_once];
+            let hooked = mark_hooked_macros(&mut e, "before_all", &args);
+            if hooked {
+                once_content.push(hooked_fn("before_all", &before_all_fn_block, &sig));
+            }
+            once_content.append(&mut e);
+
+            let matched = matched + usize::from(hooked);
+            let mut warnings = hook_warnings(&m, &once_content, "before_all", matched);
+            once_content.append(&mut warnings);
+            m.content = Some((brace, once_content));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "before_all", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will run the code in the matching `before_each` function at the beginning of every test. Useful
+/// to reset state to ensure that a test has a clean slate.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I get run at the very beginning of every test")}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+///
+/// Can be used to reduce the amount of boilerplate setup code that needs to be copied into each test.
+/// For example, if you need to ensure that tests in a single test suite are not run in parallel, this can
+/// easily be done with a [Mutex](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
+/// However, remembering to copy and paste the code to acquire a lock on the `Mutex` in every test
+/// is tedious and error prone.
+/// ```
+/// #[cfg(test)]
+/// mod without_before_each{
+///     lazy_static! {
+///         static ref MTX: Mutex<()> = Mutex::new(());
+///     }
+///     #[test]
+///     fn test_1(){let _m = MTX.lock();}
+///     #[test]
+///     fn test_2(){let _m = MTX.lock();}
+///     #[test]
+///     fn test_3(){let _m = MTX.lock();}
+/// }
+/// ```
+/// Using `before_each` removes the need to copy and paste so much and makes making changes easier
+/// because they only need to be made in a single location instead of once for every test.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod with_before_each{
+///     lazy_static! {
+///         static ref MTX: Mutex<()> = Mutex::new(());
+///     }
+///     fn before_each(){let _m = MTX.lock();}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+/// If a test panics while holding the lock, the `Mutex` is poisoned and `MTX.lock()` returns an
+/// `Err` for every test after it. For this specific case, prefer [`serial`](attr.serial.html),
+/// which isn't affected by panicking tests.
+///
+/// `before_each` can also return a value, like a lock guard or a temp dir, which is held until the
+/// test has finished and then dropped. This also works for tests generated by macros and for
+/// `with_hooks`, where the body of `before_each` isn't inserted into the test, and the value is
+/// held across the `.await`s of an async test.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod with_a_guard{
+///     use std::sync::{Mutex, MutexGuard};
+///     static MTX: Mutex<()> = Mutex::new(());
+///     fn before_each() -> MutexGuard<'static, ()> {
+///         MTX.lock().unwrap_or_else(|err| err.into_inner())
+///     }
+///     #[test]
+///     fn test_1(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn before_each(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(metadata as HookArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            reject_lifecycle_conflict(&m, &["lifecycle"])?;
+            let (brace, items) = module_items(&mut m, "before_each")?;
+            let sig = hook_sig(&items, "before_each");
+            let (before_each_fn_block, everything_else) = take_hook(&m, items, "before_each")?;
+            let matched = everything_else
+                .iter()
+                .filter(
+                    |t| matches!(t, Item::Fn(f) if hook_applies(f, "before_each", &args, &m.attrs)),
+                )
+                .count();
+
+            let mut e: Vec<Item> = everything_else
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if hook_applies(&f, "before_each", &args, &m.attrs) {
+                            mark_hooked_test(&mut f);
+                            let mut b = before_each_stmts(&before_each_fn_block, &sig);
+                            b.append(&mut f.block.stmts);
+                            f.block.stmts = b;
+                            Item::Fn(f)
+                        } else {
+                            Item::Fn(f)
+                        }
+                    }
+                    e => e,
+                })
+                .collect();
+            let hooked = mark_hooked_macros(&mut e, "before_each", &args);
+            if hooked {
+                e.push(hooked_fn("before_each", &before_each_fn_block, &sig));
+            }
+            let matched = matched + usize::from(hooked);
+            let mut warnings = hook_warnings(&m, &e, "before_each", matched);
+            e.append(&mut warnings);
+            m.content = Some((brace, e));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "before_each", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Marks a test generated by a `macro_rules!` macro defined in a mod, so the before/after hooks of
+/// the mod are applied to it. The mod attributes only see the tests written out in the mod, so
+/// tests stamped out by a macro otherwise get none of the hooks and aren't counted by `after_all`.
+/// The mod attributes fill in which hooks apply when they expand, so the attribute should be
+/// written without arguments, and the macro has to be defined inside the mod.
+///
+/// Like with [`with_hooks`](attr.with_hooks.html), the hooks are called as functions instead of
+/// having their bodies inserted into the tests.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[after_all]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I get run before `test_1` and `test_2`")}
+///     fn after_all(){println!("I get run after both of them")}
+///     macro_rules! a_test {
+///         ($name:ident) => {
+///             #[hooked]
+///             #[test]
+///             fn $name(){}
+///         };
+///     }
+///     a_test!(test_1);
+///     a_test!(test_2);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn hooked(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let hooks =
+        parse_macro_input!(metadata with Punctuated::<HookedArg, Token![,]>::parse_terminated);
+    let hooks: Vec<HookedArg> = hooks.into_iter().collect();
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Fn(mut f) => {
+            mark_hooked_test(&mut f);
+            f.block = Box::new(hooked_block(&f, &hooks));
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, "hooked", "a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will make the before/after hooks of a mod available to tests in other mods, which opt into them
+/// with [`with_hooks`](attr.with_hooks.html). Module attributes like `before_each` can't be applied
+/// to a mod whose body is in another file, like `#[cfg(test)] mod tests;`, but its tests can use
+/// hooks declared this way instead. Any of the `before_all`, `before_each`, `after_each` and
+/// `after_all` functions can be defined in the mod, and they have the same semantics as with the
+/// mod attributes: `after_all` runs once every test using the hooks has finished.
+///
+/// The hooks are called as functions instead of having their bodies inserted into the tests, so
+/// values `before_each` only binds to a variable are dropped before the test runs. A value it
+/// returns, like a lock guard, is held until the test has finished, as with the mod attributes.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[hooks]
+/// #[cfg(test)]
+/// mod db_hooks{
+///     fn before_all(){println!("I get run before the first test using these hooks")}
+///     fn after_each(){println!("I get run after every test using these hooks")}
+/// }
+///
+/// // This would usually be `mod tests;` with the tests in their own file.
+/// #[cfg(test)]
+/// mod tests{
+///     use test_env_helpers::*;
+///
+///     #[with_hooks(super::db_hooks)]
+///     #[test]
+///     fn test_1(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn hooks(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            if let Some(attr) = m.attrs.iter().find(|attr| {
+                is_attr(attr, "lifecycle") || HOOKS.iter().any(|hook| is_attr(attr, hook))
+            }) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "The `hooks` macro attribute can't be combined with the attributes that apply the hooks to the tests of the module.",
+                ));
+            }
+            let (brace, mut items) = module_items(&mut m, "hooks")?;
+            let mut hooks = vec![];
+            let mut found = false;
+            for hook in ["before_all", "before_each", "after_each", "after_all"] {
+                if let Some(Item::Fn(f)) = items
+                    .iter()
+                    .find(|t| matches!(t, Item::Fn(f) if f.sig.ident == hook))
+                {
+                    if let Some(asyncness) = &f.sig.asyncness {
+                        return Err(syn::Error::new_spanned(
+                            asyncness,
+                            format!(
+                                "The `{}` function can't be async, it is called from sync and async tests alike.",
+                                hook
+                            ),
+                        ));
+                    }
+                }
+                let sig = hook_sig(&items, hook);
+                let (block, rest) = take_optional_hook(items, hook)?;
+                items = rest;
+                found |= block.is_some();
+                hooks.push(match block {
+                    Some(block) => {
+                        let hook_fn = called_hook_fn(
+                            Ident::new("hook", Span::call_site()),
+                            hook,
+                            &block,
+                            &sig,
+                        );
+                        quote!(::std::option::Option::Some({
+                            #hook_fn
+                            hook
+                        }))
+                    }
+                    None => quote!(::std::option::Option::None),
+                });
+            }
+            if !found {
+                return Err(syn::Error::new(
+                    m.ident.span(),
+                    "The `hooks` macro attribute requires at least one of the `before_all`, `before_each`, `after_each` and `after_all` functions in the body of the module it is called on.",
+                ));
+            }
+            items.insert(
+                0,
+                parse_quote! {
+                    #[doc(hidden)]
+                    pub static __TEST_ENV_HELPERS_HOOKS: ::test_env_helpers::__private::Hooks =
+                        ::test_env_helpers::__private::Hooks::new(module_path!(), #(#hooks),*);
+                },
+            );
+            m.content = Some((brace, items));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "hooks", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Shared implementation of `before` and `after`. Mod hooks apply a test's `before` and `after`
+/// functions themselves, so this only happens for tests that no mod hook applies to.
+fn test_hook(macro_name: &str, metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = proc_macro2::TokenStream::from(metadata.clone());
+    let _ = parse_macro_input!(metadata with Punctuated::<Path, Token![,]>::parse_terminated);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Fn(mut f) => {
+            let macro_ident = format_ident!("{}", macro_name);
+            f.attrs
+                .insert(0, parse_quote!(#[::test_env_helpers::#macro_ident(#args)]));
+            apply_test_hooks(&mut f);
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, macro_name, "a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will apply all of the before/after hooks of a mod in a single pass, so the order they run in
+/// doesn't depend on the order of the attributes. Any of the `before_all`, `before_each`,
+/// `after_each` and `after_all` functions can be defined in the mod, and every test runs them as
+/// `before_all` (only for the first test), `before_each`, the test itself, `after_each`, and then
+/// `after_all` if it was the last test in the mod to finish. Like with the separate attributes,
+/// `after_each` isn't run if the test panics, but the test is still counted for `after_all`.
+///
+/// Takes the same `tags` and `test_attrs` arguments as the separate attributes, which then apply to
+/// every hook. It can't be combined with the separate attributes on the same mod.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[lifecycle]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_all(){println!("I get run first")}
+///     fn before_each(){println!("I get run before every test")}
+///     fn after_each(){println!("I get run after every test")}
+///     fn after_all(){println!("I get run last")}
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn lifecycle(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(metadata as HookArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            reject_lifecycle_conflict(&m, HOOKS)?;
+            let (brace, items) = module_items(&mut m, "lifecycle")?;
+            let before_all_sig = &hook_sig(&items, "before_all");
+            let before_each_sig = &hook_sig(&items, "before_each");
+            let after_each_sig = &hook_sig(&items, "after_each");
+            let after_all_sig = &hook_sig(&items, "after_all");
+            let (before_all, items) = take_optional_hook(items, "before_all")?;
+            let (before_each, items) = take_optional_hook(items, "before_each")?;
+            let (after_each, items) = take_optional_hook(items, "after_each")?;
+            let (after_all, items) = take_optional_hook(items, "after_all")?;
+            if before_all.is_none()
+                && before_each.is_none()
+                && after_each.is_none()
+                && after_all.is_none()
+            {
+                return Err(syn::Error::new(
+                    m.ident.span(),
+                    "The `lifecycle` macro attribute requires at least one of the `before_all`, `before_each`, `after_each` and `after_all` functions in the body of the module it is called on.",
+                ));
+            }
+            // Tests generated by macros call the hooks as functions instead.
+            let hooks = [
+                ("before_all", before_all.clone(), before_all_sig),
+                ("before_each", before_each.clone(), before_each_sig),
+                ("after_each", after_each.clone(), after_each_sig),
+                ("after_all", after_all.clone(), after_all_sig),
+            ];
+            let before_all = before_all.map(|block| before_all_once(&block));
+            let mut tests = vec![];
+            let mut matched = 0;
+
+            let mut e: Vec<Item> = items
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        let applies = |hook| hook_applies(&f, hook, &args, &m.attrs);
+                        let before_all = before_all.as_ref().filter(|_| applies("before_all"));
+                        let before_each = before_each.as_ref().filter(|_| applies("before_each"));
+                        let after_each = after_each.as_ref().filter(|_| applies("after_each"));
+                        let after_all = after_all.as_ref().filter(|_| applies("after_all"));
+                        if before_all.is_none()
+                            && before_each.is_none()
+                            && after_each.is_none()
+                            && after_all.is_none()
+                        {
+                            return Item::Fn(f);
+                        }
+                        matched += 1;
+                        mark_hooked_test(&mut f);
+                        let mut block = Block {
+                            brace_token: f.block.brace_token,
+                            stmts: vec![],
+                        };
+                        block.stmts.extend(before_all.map(|(stmt, _)| stmt.clone()));
+                        block.stmts.extend(
+                            before_each
+                                .into_iter()
+                                .flat_map(|b| before_each_stmts(b, before_each_sig)),
+                        );
+                        match after_each {
+                            Some(after_each) if takes_outcome(after_each_sig) => {
+                                // `before_each` stays outside, so what it binds lives until the
+                                // end of the test.
+                                let wrapped = outcome_block(&f, &f.block, after_each);
+                                block.stmts.extend(wrapped.stmts);
+                            }
+                            Some(after_each) => {
+                                block.stmts.append(&mut f.block.stmts);
+                                append_stmts(&mut block, &after_each_stmts(after_each));
+                            }
+                            None => block.stmts.append(&mut f.block.stmts),
+                        }
+                        if let Some(after_all) = after_all {
+                            tests.push(counted_test(&f, &args, &m.attrs));
+                            block = after_all_block(&f, &block, after_all);
+                        }
+                        f.block = Box::new(block);
+                        Item::Fn(f)
+                    }
+                    e => e,
+                })
+                .collect();
+
+            let mut content = vec![];
+            content.extend(before_all.map(|(_, once)| once));
+            if after_all.is_some() {
+                content.append(&mut after_all_statics(&tests));
+            }
+            for (hook, block, sig) in hooks {
+                if let Some(block) = block {
+                    if mark_hooked_macros(&mut e, hook, &args) {
+                        matched += 1;
+                        content.push(hooked_fn(hook, &block, sig));
+                    }
+                }
+            }
+            content.append(&mut e);
+            if matched == 0 {
+                content.push(warning(
+                    m.ident.span(),
+                    "hook_matches_no_tests",
+                    "The `lifecycle` hooks don't apply to any test in this module, so they are never run.",
+                ));
+            }
+            m.content = Some((brace, content));
+            Ok(Item::Mod(m))
+        }
+        item => Err(unsupported_item(&item, "lifecycle", "a module")),
+    });
+    TokenStream::from(output)
+}
+
+/// Registers the
+/// [`TestListener`](https://docs.rs/test-env-helpers/latest/test_env_helpers/trait.TestListener.html)
+/// held by a static, so it is called whenever a test with any of the hooks or tags applied, or one
+/// of its hooks, starts or ends. Every test binary calls the listeners registered in it, wherever
+/// they are declared, so a listener only has to be registered once, e.g. in a shared module.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[cfg(test)]
+/// struct Printer;
+///
+/// #[cfg(test)]
+/// impl TestListener for Printer {
+///     fn on_test_start(&self, test: &TestInfo){println!("starting {}", test.full_name())}
+/// }
+///
+/// #[listener]
+/// #[cfg(test)]
+/// static PRINTER: Printer = Printer;
+/// ```
+#[proc_macro_attribute]
+pub fn listener(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Static(s) => {
+            let ident = &s.ident;
+            let cfgs = s.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            Ok(Item::Verbatim(quote! {
+                #s
+                #(#cfgs)*
+                ::test_env_helpers::__private::submit! {
+                    ::test_env_helpers::__private::RegisteredListener::new(&#ident)
+                }
+            }))
+        }
+        item => Err(unsupported_item(&item, "listener", "a static")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will limit how many tests run at the same time. Useful for tests that can run in parallel with
+/// each other, but not with unlimited threads, e.g. because each one starts a server on a port from
+/// a small range. A test holds its slot for its whole run, including any code inserted by the
+/// before/after hooks, and gives it back even if it panics. If a test has to wait more than a
+/// second for a slot, how long it waited is printed.
+///
+/// Valid on a mod or an individual test. Without a group, at most `n` tests from the same mod run
+/// at once. With `group = "name"`, at most `n` tests from the same group run at once, no matter
+/// which mod they are in. Every test in a group should use the same limit.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[max_concurrency(2)]
+/// #[cfg(test)]
+/// mod my_tests{
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+///
+/// #[max_concurrency(4, group = "servers")]
+/// #[cfg(test)]
+/// mod server_tests{
+///     #[test]
+///     fn starts_a_server(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn max_concurrency(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    limit_concurrency("max_concurrency", metadata, input)
+}
+
+/// Marks a test that none of the before/after hooks of its mod should be applied to. Useful for a
+/// test that checks how things behave before they have been set up. A test marked with `no_hooks`
+/// isn't counted by `after_all`, so `after_all` may run before it does.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_all]
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_all(){println!("I don't get run before `uninitialized`")}
+///     fn before_each(){println!("I don't get run before `uninitialized` either")}
+///     #[no_hooks]
+///     #[test]
+///     fn uninitialized(){}
+///     #[test]
+///     fn initialized(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn no_hooks(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+    marker("no_hooks", input)
+}
+
+/// Will run a test the given number of times and fail on the first iteration that fails, printing
+/// the index of that iteration. Useful for shaking out race conditions and other flakiness. Like
+/// `retry`, any code inserted by `before_each` and `after_each` is run for every iteration.
+///
+/// Valid on a mod, where it applies to every test in the mod that doesn't have its own `repeat`, or
+/// on an individual test. Tests in a mod that has any of the before/after hooks applied are also
+/// repeated when the `TEST_ENV_HELPERS_REPEAT` environment variable is set, e.g.
+/// `TEST_ENV_HELPERS_REPEAT=100 cargo test`. Because the body may be run more than once, tests that
+/// take arguments, like `#[test_case]`s, are only repeated by an explicit `repeat` and then must
+/// not move their arguments.
+///
+/// Tests that generate random data can get a different seed for every iteration from
+/// [`seed`](fn.seed.html). The seed of a failing iteration is printed along with its index.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[repeat(100)]
+/// #[cfg(test)]
+/// mod my_tests{
+///     #[test]
+///     fn racy_test(){}
+///     #[repeat(1000)]
+///     #[test]
+///     fn very_racy_test(){let _ = seed();}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn repeat(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let (metadata, test_attrs) = split_test_attrs!(metadata);
+    let iterations = if metadata.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(metadata as LitInt))
+    };
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            let iterations = iterations.ok_or_else(|| {
+                syn::Error::new(
+                    Span::call_site(),
+                    "The `repeat` macro attribute requires the number of iterations when called on a module, like `#[repeat(100)]`.",
+                )
+            })?;
+            let (brace, items) = module_items(&mut m, "repeat")?;
+            let e: Vec<Item> = items
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if is_test(&f, &test_attrs) {
+                            // Hooks may already have marked the test to be repeated by the env var.
+                            f.attrs.retain(|attr| {
+                                !(is_attr(attr, "repeat") && attr.tokens.is_empty())
+                            });
+                            if !f.attrs.iter().any(|attr| is_attr(attr, "repeat")) {
+                                f.attrs.insert(
+                                    0,
+                                    parse_quote!(#[::test_env_helpers::repeat(#iterations)]),
+                                );
+                            }
+                        }
+                        Item::Fn(f)
+                    }
+                    e => e,
+                })
+                .collect();
+            m.content = Some((brace, e));
+            Ok(Item::Mod(m))
+        }
+        Item::Fn(mut f) => {
+            let name = test_name(&f);
+            let should_panic = should_panic(&f);
+            let driver = match iterations {
+                Some(iterations) => quote! {
+                    ::test_env_helpers::__private::Repeat::new(#name, #iterations, #should_panic)
+                },
+                None => quote! {
+                    ::test_env_helpers::__private::Repeat::from_env(#name, #should_panic)
+                },
+            };
+            f.block = Box::new(rerun_block(&f, driver));
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, "repeat", "a module or a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will re-run a failing test up to the given number of times. A test fails if it panics or returns
+/// an `Err`. Any code inserted by `before_each` and `after_each` is part of the test, so the hooks
+/// are run again for every attempt, and `after_all` only counts the test as finished after its
+/// final attempt. When a test only passes after being retried, the attempt it passed on is printed.
+///
+/// Valid on a mod, where it applies to every test in the mod that doesn't have its own `retry`, or
+/// on an individual test. Setting the `TEST_ENV_HELPERS_NO_RETRY` environment variable disables
+/// retries entirely, which is useful when you want flaky tests to show up as failures.
+///
+/// Because the body of the test is run more than once, it can't move any of the test's arguments.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[retry(3)]
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I get run before every attempt")}
+///     #[test]
+///     fn flaky_test(){}
+///     #[retry(10)]
+///     #[test]
+///     fn very_flaky_test(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn retry(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let (metadata, test_attrs) = split_test_attrs!(metadata);
+    let attempts = parse_macro_input!(metadata as LitInt);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            let (brace, items) = module_items(&mut m, "retry")?;
+            let e = mark_tests(
+                items,
+                "retry",
+                parse_quote!(#[::test_env_helpers::retry(#attempts)]),
+                &test_attrs,
+            );
+            m.content = Some((brace, e));
+            Ok(Item::Mod(m))
+        }
+        Item::Fn(mut f) => {
+            let name = test_name(&f);
+            let should_panic = should_panic(&f);
+            f.block = Box::new(rerun_block(
+                &f,
+                quote!(::test_env_helpers::__private::Retry::new(
+                    #name,
+                    #attempts,
+                    #should_panic
+                )),
+            ));
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, "retry", "a module or a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will prevent tests from running at the same time as each other. The lock is held for the whole
+/// test, including any code inserted by the before/after hooks, and is released even if the test
+/// panics, so one failing test doesn't cause the rest to fail.
+///
+/// Valid on a mod or an individual test. Without arguments, a test won't run at the same time as
+/// any other `serial` test in the same mod. With `group = "name"`, a test won't run at the same
+/// time as any other test in the same group, no matter which mod it is in.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[serial]
+/// #[cfg(test)]
+/// mod my_tests{
+///     #[test]
+///     fn test_1(){}
+///     #[test]
+///     fn test_2(){}
+/// }
+///
+/// #[serial(group = "db")]
+/// #[cfg(test)]
+/// mod db_tests{
+///     #[test]
+///     fn i_have_the_db_to_myself(){}
+/// }
+///
+/// #[cfg(test)]
+/// mod other_tests{
+///     #[serial(group = "db")]
+///     #[test]
+///     fn me_too(){}
+///     #[test]
+///     fn i_run_whenever(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn serial(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    limit_concurrency("serial", metadata, input)
+}
+
+/// Shared implementation of `serial` and `max_concurrency`, which only differ in how many tests are
+/// allowed to hold a permit at once.
+fn limit_concurrency(macro_name: &str, metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let (metadata, test_attrs) = split_test_attrs!(metadata);
+    let args = proc_macro2::TokenStream::from(metadata.clone());
+    let attribute_args = parse_macro_input!(metadata as AttributeArgs);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| {
+        let (limit, group) = limit_args(attribute_args, macro_name)?;
+        let permits = if macro_name == "serial" {
+            if let Some(limit) = limit {
+                return Err(syn::Error::new_spanned(
+                    limit,
+                    "The `serial` macro attribute doesn't take a limit, use `max_concurrency` instead.",
+                ));
+            }
+            quote!(1)
+        } else {
+            let limit = limit.ok_or_else(|| {
+                syn::Error::new(
+                    Span::call_site(),
+                    "The `max_concurrency` macro attribute requires a limit, like `#[max_concurrency(2)]`.",
+                )
+            })?;
+            quote!(#limit)
+        };
+        match input {
+            Item::Mod(mut m) => {
+                let (brace, items) = module_items(&mut m, macro_name)?;
+                let macro_ident = format_ident!("{}", macro_name);
+                let e = mark_tests(
+                    items,
+                    macro_name,
+                    parse_quote!(#[::test_env_helpers::#macro_ident(#args)]),
+                    &test_attrs,
+                );
+                m.content = Some((brace, e));
+                Ok(Item::Mod(m))
+            }
+            Item::Fn(mut f) => {
+                let key = match group {
+                    Some(group) => {
+                        let prefix = format!("{} group ", macro_name);
+                        quote!(concat!(#prefix, #group))
+                    }
+                    None => {
+                        let prefix = format!("{} ", macro_name);
+                        quote!(concat!(#prefix, module_path!()))
+                    }
+                };
+                let name = test_name(&f);
+                let block = &f.block;
+                let permit = Ident::new("_permit", Span::mixed_site());
+                f.block = Box::new(parse_quote!({
+                    let #permit =
+                        ::test_env_helpers::__private::Permit::acquire(#name, #key, #permits);
+                    #block
+                }));
+                Ok(Item::Fn(f))
+            }
+            item => Err(unsupported_item(&item, macro_name, "a module or a test")),
+        }
+    });
+    TokenStream::from(output)
+}
+
+/// Will skip running the code it is applied on. You can use it to skip tests that aren't working
+/// correctly or that you don't want to run for some reason. It will remove whatever it is applied
+/// to from the final AST, and warns when that isn't a `#[test]` or mod.
+///
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[cfg(test)]
+/// mod my_tests{
+///     #[skip]
+///     #[test]
+///     fn broken_test(){panic!("I'm hella broke")}
+///     #[skip]
+///     mod broken_mod{
+///         #[test]
+///         fn i_will_not_be_run(){panic!("I get skipped too")}
+///     }
+///     #[test]
+///     fn test_2(){}
+///     #[test]
+///     fn test_3(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn skip(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let (_, test_attrs) = split_test_attrs!(metadata);
+    let input = parse_macro_input!(input as Item);
+    // A fn with an attribute other than a doc comment may be a test whose attribute isn't built in.
+    let warning = match &input {
+        Item::Mod(_) => None,
+        Item::Fn(f)
+            if is_test(f, &test_attrs) || f.attrs.iter().any(|attr| !attr.path.is_ident("doc")) =>
+        {
+            None
+        }
+        item => Some(warning(
+            item.span(),
+            "skip_on_non_test",
+            "The `skip` macro attribute is meant for tests and modules, but this is neither.",
+        )),
+    };
+    let skipped = skipped_tests(&input, &test_attrs);
+    TokenStream::from(quote! {#warning #skipped})
+}
+
+/// Marks a test that the `after_each` hook of its mod shouldn't be applied to. Other hooks are
+/// still applied.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[after_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn after_each(){println!("I don't get run after `keeps_its_mess`")}
+///     #[skip_after_each]
+///     #[test]
+///     fn keeps_its_mess(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn skip_after_each(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+    marker("skip_after_each", input)
+}
+
+/// Marks a test that the `before_each` hook of its mod shouldn't be applied to. Other hooks are
+/// still applied.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I don't get run before `starts_dirty`")}
+///     #[skip_before_each]
+///     #[test]
+///     fn starts_dirty(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn skip_before_each(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+    marker("skip_before_each", input)
+}
+
+/// Marker attributes only mean something to the mod level macros, which read them before the
+/// marked test is expanded. By the time the marker itself is expanded it has done its job.
+fn marker(macro_name: &str, input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Fn(f) => Ok(Item::Fn(f)),
+        item => Err(unsupported_item(&item, macro_name, "a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Tags a test, or every test in a mod, so that it can be selected at runtime and so that hooks can
+/// be limited to tests with certain tags. When the `TEST_TAGS` environment variable is set, tests
+/// that don't match it return early without running any hooks. `TEST_TAGS` is a comma separated
+/// list of tags, where tags prefixed with a `!` exclude tests, e.g. `TEST_TAGS=db,!slow` runs the
+/// tests tagged `db` that aren't tagged `slow`. Untagged tests in a mod with any of the
+/// before/after hooks applied are also skipped when `TEST_TAGS` includes any tags.
+///
+/// The hooks only apply to tests with one of the given tags when called with `tags = [..]`. A mod
+/// can then hold tests with different setup needs.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[before_each(tags = [db])]
+/// #[cfg(test)]
+/// mod my_tests{
+///     fn before_each(){println!("I only get run before tests tagged `db`")}
+///     #[tag(db)]
+///     #[test]
+///     fn uses_the_db(){}
+///     #[tag(db, slow)]
+///     #[test]
+///     fn uses_the_db_a_lot(){}
+///     #[test]
+///     fn doesnt_use_the_db(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn tag(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let (metadata, test_attrs) = split_test_attrs!(metadata);
+    let metadata = proc_macro2::TokenStream::from(metadata);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Mod(mut m) => {
+            tag_names(metadata.clone())?;
+            let (brace, items) = module_items(&mut m, "tag")?;
+            let e: Vec<Item> = items
+                .into_iter()
+                .map(|t| match t {
+                    Item::Fn(mut f) => {
+                        if is_test(&f, &test_attrs) {
+                            f.attrs
+                                .insert(0, parse_quote!(#[::test_env_helpers::tag(#metadata)]));
+                        }
+                        Item::Fn(f)
+                    }
+                    e => e,
+                })
+                .collect();
+            m.content = Some((brace, e));
+            Ok(Item::Mod(m))
+        }
+        Item::Fn(mut f) => {
+            // The first `tag` to be expanded handles the rest, so the test is only checked once.
+            let mut tags = test_tags(&f, &[]);
+            tags.extend(tag_names(metadata.clone())?);
+            tags.sort();
+            tags.dedup();
+            f.attrs.retain(|attr| !is_attr(attr, "tag"));
+            let name = test_name(&f);
+            f.block = Box::new(test_scope(&f, &tags, |scope| {
+                quote! {
+                    if !::test_env_helpers::__private::run_tagged(#name, &[#(#tags),*]) {
+                        #scope.skip();
+                        return ::test_env_helpers::__private::TestReturn::skipped();
+                    }
+                }
+            }));
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, "tag", "a module or a test")),
+    });
+    TokenStream::from(output)
+}
+
+/// Will run the hooks declared by a mod with [`hooks`](attr.hooks.html) applied around a single
+/// test. This is how tests in a mod whose body is in another file, like `#[cfg(test)] mod tests;`,
+/// can use hooks: every test opts in individually, and `after_all` runs once every test that opted
+/// in has finished. A test can still opt out of `before_each` or `after_each` with
+/// `#[skip_before_each]` or `#[skip_after_each]`.
+/// ```
+/// #[cfg(test)]
+/// use test_env_helpers::*;
+///
+/// #[hooks]
+/// #[cfg(test)]
+/// mod shared_hooks{
+///     fn before_each(){println!("I get run before every test using these hooks")}
+/// }
+///
+/// #[cfg(test)]
+/// mod tests{
+///     use test_env_helpers::*;
+///
+///     #[with_hooks(super::shared_hooks)]
+///     #[test]
+///     fn test_1(){}
+///     #[with_hooks(super::shared_hooks)]
+///     #[tokio::test]
+///     async fn test_2(){}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn with_hooks(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(metadata as Path);
+    let input = parse_macro_input!(input as Item);
+    let output = expansion(input, |input| match input {
+        Item::Fn(mut f) => {
+            mark_hooked_test(&mut f);
+            f.block = Box::new(with_hooks_block(&f, &path));
+            Ok(Item::Fn(f))
+        }
+        item => Err(unsupported_item(&item, "with_hooks", "a test")),
+    });
+    TokenStream::from(output)
+}
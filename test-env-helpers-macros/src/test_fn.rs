@@ -0,0 +1,116 @@
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, FnArg, ItemFn, Path, Token};
+
+/// Attributes that turn a function into a single test.
+const TEST_ATTRS: &[&str] = &[
+    "test",
+    "tokio::test",
+    "async_std::test",
+    "test_log::test",
+    "actix_rt::test",
+    "actix_web::test",
+    "sqlx::test",
+    "wasm_bindgen_test",
+    "wasm_bindgen_test::wasm_bindgen_test",
+];
+
+/// Attributes that turn a function into one test per attribute.
+const TEST_CASE_ATTRS: &[&str] = &["test_case", "test_case::test_case"];
+
+/// Attributes that turn a function into one test per `#[case]` and `#[values]` combination.
+const RSTEST_ATTRS: &[&str] = &["rstest", "rstest::rstest"];
+
+fn path_string(path: &Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn is_one_of(attr: &Attribute, paths: &[&str]) -> bool {
+    paths.contains(&path_string(&attr.path).as_str())
+}
+
+/// The number of comma separated arguments of an attribute, e.g. 3 for `#[values(1, 2, 3)]`.
+fn arg_count(attr: &Attribute) -> usize {
+    attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        .map_or(0, |args| args.len())
+}
+
+/// `rstest` generates a test for every combination of its `#[case]`s and the `#[values]` of each
+/// argument, e.g. `#[case::first(1)]`, or just one test if it has neither.
+fn rstest_count(f: &ItemFn) -> usize {
+    let cases = f
+        .attrs
+        .iter()
+        .filter(|attr| {
+            attr.path
+                .segments
+                .first()
+                .is_some_and(|segment| segment.ident == "case")
+                && attr.path.segments.len() <= 2
+        })
+        .count();
+    let values: usize = f
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => arg.attrs.iter().find(|attr| attr.path.is_ident("values")),
+            FnArg::Receiver(_) => None,
+        })
+        .map(arg_count)
+        .product();
+    cases.max(1) * values
+}
+
+/// Returns the number of tests that the attributes of `f` turn it into. A function is recognized as
+/// a test if it has one of the built in test attributes, like `#[test]`, `#[tokio::test]`,
+/// `#[test_case(..)]` or `#[rstest]`, or one of the `extra` attributes. `#[test_case(..)]` and
+/// `#[rstest]` with `#[case(..)]`s or `#[values(..)]` generate more than one test, and only their
+/// cases count when they are combined with a runtime attribute like `#[tokio::test]`. A test marked
+/// `#[skip]` is removed, so it doesn't count.
+pub fn test_count(f: &ItemFn, extra: &[Path]) -> usize {
+    if is_skipped(f) {
+        return 0;
+    }
+    let test_cases = f
+        .attrs
+        .iter()
+        .filter(|attr| is_one_of(attr, TEST_CASE_ATTRS))
+        .count();
+    if test_cases > 0 {
+        return test_cases;
+    }
+    if f.attrs.iter().any(|attr| is_one_of(attr, RSTEST_ATTRS)) {
+        return rstest_count(f);
+    }
+    let is_test = f.attrs.iter().any(|attr| {
+        is_one_of(attr, TEST_ATTRS)
+            || extra
+                .iter()
+                .any(|path| path_string(path) == path_string(&attr.path))
+    });
+    usize::from(is_test)
+}
+
+/// Returns `true` if `f` is marked `#[skip]`, which removes it.
+pub fn is_skipped(f: &ItemFn) -> bool {
+    f.attrs.iter().any(|attr| {
+        attr.path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "skip")
+    })
+}
+
+/// Returns `true` if `f` is marked `#[ignore]`, so the test harness only runs it when asked to.
+pub fn is_ignored(f: &ItemFn) -> bool {
+    f.attrs.iter().any(|attr| attr.path.is_ident("ignore"))
+}
+
+/// Returns `true` if `f` is a test, see [`test_count`].
+pub fn is_test(f: &ItemFn, extra: &[Path]) -> bool {
+    test_count(f, extra) > 0
+}
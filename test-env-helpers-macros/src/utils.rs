@@ -0,0 +1,1342 @@
+use crate::test_fn::{is_ignored, is_skipped, is_test, test_count};
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{
+    bracketed, parse_quote, token, Attribute, AttributeArgs, Block, FnArg, Item, ItemFn, ItemMod,
+    Lit, LitInt, LitStr, Meta, MetaNameValue, NestedMeta, Pat, PatType, Path, ReturnType,
+    Signature, Stmt, Token, Type,
+};
+
+/// Runs `expand` on the item a macro attribute is applied to. If it fails, the error is emitted
+/// along with the unchanged item, so it isn't buried under errors caused by the item disappearing.
+pub fn expansion(input: Item, expand: impl FnOnce(Item) -> syn::Result<Item>) -> TokenStream {
+    match expand(input.clone()) {
+        Ok(output) => output.into_token_stream(),
+        Err(err) => {
+            let err = err.to_compile_error();
+            quote!(#err #input)
+        }
+    }
+}
+
+/// The error for a macro attribute applied to an item it doesn't support, e.g. a hook on a struct.
+pub fn unsupported_item(item: &Item, macro_name: &str, valid_on: &str) -> syn::Error {
+    syn::Error::new_spanned(
+        item,
+        format!(
+            "The `{}` macro attribute is only valid when called on {}.",
+            macro_name, valid_on
+        ),
+    )
+}
+
+/// Takes the items out of a module a macro attribute is applied to, which fails for modules whose
+/// body lives in another file.
+pub fn module_items(m: &mut ItemMod, macro_name: &str) -> syn::Result<(token::Brace, Vec<Item>)> {
+    m.content.take().ok_or_else(|| {
+        syn::Error::new_spanned(
+            &*m,
+            format!(
+                "The `{}` macro attribute can't be used on a module without a body, like `mod tests;`. Declare the tests inline with `mod tests {{ .. }}` instead.",
+                macro_name
+            ),
+        )
+    })
+}
+
+/// Fails if `m` is also marked with one of the `others` attributes, since `lifecycle` already
+/// applies every hook and can't be combined with the separate hook attributes in either order.
+pub fn reject_lifecycle_conflict(m: &ItemMod, others: &[&str]) -> syn::Result<()> {
+    match m
+        .attrs
+        .iter()
+        .find(|attr| others.iter().any(|other| is_attr(attr, other)))
+    {
+        Some(attr) => Err(syn::Error::new_spanned(
+            attr,
+            "The `lifecycle` macro attribute already applies every hook, so it can't be combined with the separate hook attributes.",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Removes the function called `hook` from the items of `m` and returns its body along with the
+/// remaining items. There must be exactly one, with a signature that allows its body to be inserted
+/// into the tests.
+pub fn take_hook(m: &ItemMod, items: Vec<Item>, hook: &str) -> syn::Result<(Block, Vec<Item>)> {
+    match take_optional_hook(items, hook)? {
+        (Some(block), everything_else) => Ok((block, everything_else)),
+        (None, _) => Err(syn::Error::new(
+            m.ident.span(),
+            format!(
+                "The `{0}` macro attribute requires a function named `{0}` in the body of the module it is called on.",
+                hook
+            ),
+        )),
+    }
+}
+
+/// Like [`take_hook`], but the module doesn't have to have the hook function.
+pub fn take_optional_hook(items: Vec<Item>, hook: &str) -> syn::Result<(Option<Block>, Vec<Item>)> {
+    let (hook_fns, everything_else): (Vec<Item>, Vec<Item>) =
+        items.into_iter().partition(|t| match t {
+            Item::Fn(f) => f.sig.ident == hook,
+            _ => false,
+        });
+    let mut hook_fns = hook_fns.into_iter().map(|t| match t {
+        Item::Fn(f) => f,
+        _ => unreachable!(),
+    });
+    let hook_fn = match hook_fns.next() {
+        Some(hook_fn) => hook_fn,
+        None => return Ok((None, everything_else)),
+    };
+    let mut errors = hook_fns.map(|f| {
+        syn::Error::new(
+            f.sig.ident.span(),
+            format!(
+                "The `{0}` macro attribute requires a single function named `{0}`, but there is more than one.",
+                hook
+            ),
+        )
+    });
+    if let Some(mut err) = errors.next() {
+        errors.for_each(|other| err.combine(other));
+        return Err(err);
+    }
+    validate_hook(&hook_fn, hook)?;
+    Ok((Some(hook_block(hook_fn)), everything_else))
+}
+
+/// Returns the body of the hook function `f`, binding its parameters to the info of the test it
+/// runs for, to the [`outcome`] of the test for `after_each`, and to the [`summary`] of the mod for
+/// `after_all`. The body reports when the hook starts and ends, so a test that fails in between is
+/// known to have failed in the hook.
+fn hook_block(f: ItemFn) -> Block {
+    let mut block = *f.block;
+    let [outcome, summary] = [outcome(), summary()];
+    let bindings = f.sig.inputs.iter().filter_map(|input| match input {
+        FnArg::Typed(PatType { pat, ty, .. }) if is_ref_to(ty, "TestOutcome") => {
+            Some(parse_quote!(let #pat: #ty = #outcome;))
+        }
+        FnArg::Typed(PatType { pat, ty, .. }) if is_ref_to(ty, "ModuleSummary") => {
+            Some(parse_quote!(let #pat: #ty = #summary;))
+        }
+        FnArg::Typed(PatType { pat, ty, .. }) => {
+            Some(parse_quote!(let #pat: #ty = &::test_env_helpers::__private::test_info();))
+        }
+        FnArg::Receiver(_) => None,
+    });
+    block.stmts.splice(0..0, bindings.collect::<Vec<Stmt>>());
+    let hook = f.sig.ident.to_string();
+    // Spanned so `line!()` is the line the hook is defined on.
+    let location = quote_spanned!(f.sig.ident.span()=> concat!(file!(), ":", line!()));
+    block.stmts.insert(
+        0,
+        parse_quote!(::test_env_helpers::__private::enter_hook(#hook, #location);),
+    );
+    // A hook that always panics never reaches its end.
+    let exit_hook: Stmt = parse_quote! {
+        #[allow(unreachable_code)]
+        ::test_env_helpers::__private::exit_hook();
+    };
+    match block.stmts.pop() {
+        // A returned value, like the guard of `before_each`, is returned once the hook has ended.
+        Some(Stmt::Expr(tail)) if returns_guard(&f.sig) => {
+            let value = Ident::new("value", Span::mixed_site());
+            block.stmts.push(parse_quote!(let #value = #tail;));
+            block.stmts.push(exit_hook);
+            block.stmts.push(Stmt::Expr(parse_quote!(
+                #[allow(unreachable_code)]
+                #value
+            )));
+        }
+        Some(Stmt::Expr(tail)) => {
+            block.stmts.push(Stmt::Semi(tail, Default::default()));
+            block.stmts.push(exit_hook);
+        }
+        stmt => {
+            block.stmts.extend(stmt);
+            block.stmts.push(exit_hook);
+        }
+    }
+    block
+}
+
+/// The `&TestOutcome` variable that `after_each` hooks taking the outcome of the test are bound
+/// to, which the code running them has to define.
+pub fn outcome() -> Ident {
+    Ident::new("outcome", Span::mixed_site())
+}
+
+/// The `&ModuleSummary` variable that `after_all` hooks taking the summary of the mod are bound
+/// to, which the code running them has to define.
+pub fn summary() -> Ident {
+    Ident::new("summary", Span::mixed_site())
+}
+
+/// Returns the signature of the function called `hook` among `items`, which decides how the hook
+/// is run, e.g. whether `after_each` takes the outcome of the test. Defaults to `fn hook()` when
+/// there is no such function.
+pub fn hook_sig(items: &[Item], hook: &str) -> Signature {
+    items
+        .iter()
+        .find_map(|item| match item {
+            Item::Fn(f) if f.sig.ident == hook => Some(f.sig.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| {
+            let hook = Ident::new(hook, Span::call_site());
+            parse_quote!(fn #hook())
+        })
+}
+
+/// Returns `true` if `sig` takes a `&TestOutcome`, so the hook is also run for tests that panicked.
+pub fn takes_outcome(sig: &Signature) -> bool {
+    sig.inputs
+        .iter()
+        .any(|input| matches!(input, FnArg::Typed(arg) if is_ref_to(&arg.ty, "TestOutcome")))
+}
+
+/// Returns `true` if `sig` returns a value, which for `before_each` is a guard that is held until
+/// the test has finished.
+pub fn returns_guard(sig: &Signature) -> bool {
+    match &sig.output {
+        ReturnType::Default => false,
+        ReturnType::Type(_, ty) => !matches!(&**ty, Type::Tuple(unit) if unit.elems.is_empty()),
+    }
+}
+
+/// The statements that run `before_each`, whose body is `block` and signature is `sig`, at the
+/// start of a test. A returned guard is bound to a variable, so it is held until the end of the
+/// test, while the statements of other hooks are inserted as they are. The body is wrapped in a
+/// closure or async block, so a `return` in it only returns from the hook.
+pub fn before_each_stmts(block: &Block, sig: &Signature) -> Vec<Stmt> {
+    let ty = match &sig.output {
+        ReturnType::Type(_, ty) if returns_guard(sig) => ty,
+        _ => return block.stmts.clone(),
+    };
+    let guard = Ident::new("_guard", Span::mixed_site());
+    // A type like `impl Drop` can't be written on a `let` or a closure, so it is inferred instead.
+    let ty = Some(ty).filter(|ty| !mentions_impl_trait(&ty.to_token_stream()));
+    match (sig.asyncness.is_some(), ty) {
+        (true, Some(ty)) => vec![parse_quote!(let #guard: #ty = async #block.await;)],
+        (true, None) => vec![parse_quote!(let #guard = async #block.await;)],
+        (false, Some(ty)) => vec![parse_quote!(let #guard = (|| -> #ty #block)();)],
+        (false, None) => vec![parse_quote!(let #guard = (|| #block)();)],
+    }
+}
+
+fn mentions_impl_trait(tokens: &TokenStream) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => mentions_impl_trait(&group.stream()),
+        _ => false,
+    })
+}
+
+/// Returns `true` if `ty` is a reference to the type called `name`, however it was imported, e.g.
+/// `&TestInfo`.
+fn is_ref_to(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Reference(reference) => matches!(
+            &*reference.elem,
+            Type::Path(path) if path.path.segments.last().is_some_and(|last| last.ident == name)
+        ),
+        _ => false,
+    }
+}
+
+/// Checks that the signature of a hook function allows its body to be inserted into the tests.
+fn validate_hook(f: &ItemFn, hook: &str) -> syn::Result<()> {
+    let mut errors = vec![];
+    if !f.sig.generics.params.is_empty() || f.sig.generics.where_clause.is_some() {
+        errors.push(syn::Error::new_spanned(
+            &f.sig.generics,
+            format!("The `{}` function can't be generic.", hook),
+        ));
+    }
+    let mut params = vec![];
+    if hook.ends_with("_each") {
+        params.push("TestInfo");
+    }
+    if hook == "after_each" {
+        params.push("TestOutcome");
+    }
+    if hook == "after_all" {
+        params.push("ModuleSummary");
+    }
+    for input in &f.sig.inputs {
+        let param = params
+            .iter()
+            .position(|param| matches!(input, FnArg::Typed(arg) if is_ref_to(&arg.ty, param)));
+        if let Some(param) = param {
+            // Each parameter can only be taken once.
+            params.remove(param);
+            continue;
+        }
+        let message = match hook {
+            "before_each" => format!(
+                "The `{}` function can only take a `&TestInfo`, its body is inserted into the tests.",
+                hook
+            ),
+            "after_each" => format!(
+                "The `{}` function can only take a `&TestInfo` and a `&TestOutcome`, its body is inserted into the tests.",
+                hook
+            ),
+            "after_all" => format!(
+                "The `{}` function can only take a `&ModuleSummary`, its body is inserted into the tests.",
+                hook
+            ),
+            _ => format!(
+                "The `{}` function can't take arguments, its body is inserted into the tests.",
+                hook
+            ),
+        };
+        errors.push(syn::Error::new_spanned(input, message));
+    }
+    if let (ReturnType::Type(_, ty), true) = (&f.sig.output, hook != "before_each") {
+        if returns_guard(&f.sig) {
+            errors.push(syn::Error::new_spanned(
+                ty,
+                format!(
+                    "The `{}` function can't return a value, its body is inserted into the tests.",
+                    hook
+                ),
+            ));
+        }
+    }
+    if let (Some(asyncness), true) = (&f.sig.asyncness, hook.ends_with("_all")) {
+        errors.push(syn::Error::new_spanned(
+            asyncness,
+            format!(
+                "The `{}` function can't be async, it is run once for sync and async tests alike.",
+                hook
+            ),
+        ));
+    }
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(mut err) => {
+            errors.for_each(|other| err.combine(other));
+            Err(err)
+        }
+        None => Ok(()),
+    }
+}
+
+/// The names of the hook functions, which are also the names of their attributes.
+pub const HOOKS: &[&str] = &["after_all", "after_each", "before_all", "before_each"];
+
+/// Emits a warning pointing at `span`. Proc macros can't emit warnings on stable, but using a
+/// deprecated item can, so this defines one with `message` as its note and uses it right away.
+pub fn warning(span: Span, name: &str, message: &str) -> Item {
+    let ident = Ident::new(name, span);
+    parse_quote! {
+        const _: () = {
+            #[deprecated(note = #message)]
+            #[allow(non_camel_case_types)]
+            struct #ident;
+            let _ = #ident;
+        };
+    }
+}
+
+/// Returns warnings for suspicious uses of `hook` on `m`, whose items after expanding the hook are
+/// `items`: a hook that applies to none of the tests is never run, and a hook function left in the
+/// module once the last hook attribute has been expanded is never run either.
+pub fn hook_warnings(m: &ItemMod, items: &[Item], hook: &str, matched: usize) -> Vec<Item> {
+    let mut warnings = vec![];
+    if matched == 0 {
+        warnings.push(warning(
+            m.ident.span(),
+            "hook_matches_no_tests",
+            &format!(
+                "The `{}` hook doesn't apply to any test in this module, so it is never run.",
+                hook
+            ),
+        ));
+    }
+    if !m
+        .attrs
+        .iter()
+        .any(|attr| is_attr(attr, "lifecycle") || HOOKS.iter().any(|hook| is_attr(attr, hook)))
+    {
+        for item in items {
+            if let Item::Fn(f) = item {
+                if HOOKS.iter().any(|hook| f.sig.ident == hook) {
+                    warnings.push(warning(
+                        f.sig.ident.span(),
+                        "hook_without_attribute",
+                        &format!(
+                            "`{0}` looks like a hook, but the module doesn't have the `#[{0}]` attribute, so it is never run.",
+                            f.sig.ident
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Returns `true` if `attr` is the attribute called `name`, however it was imported, e.g. both
+/// `#[retry(3)]` and `#[::test_env_helpers::retry(3)]` are called `retry`.
+pub fn is_attr(attr: &Attribute, name: &str) -> bool {
+    attr.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == name)
+}
+
+/// Returns `true` if `f` is marked with `no_hooks`, or with the `skip_*` marker for `hook`.
+pub fn skips_hook(f: &ItemFn, hook: &str) -> bool {
+    let marker = format!("skip_{}", hook);
+    f.attrs
+        .iter()
+        .any(|attr| is_attr(attr, "no_hooks") || is_attr(attr, &marker))
+}
+
+/// Adds `attr` to the front of the attributes of every test in `items` that doesn't already have an
+/// attribute called `name`, where `test_attrs` are the attributes marking tests besides the built
+/// in ones. Module attributes like `#[retry(3)]` use this to apply themselves to each test: the
+/// test's own attributes are expanded after every module attribute, so they wrap any code the hooks
+/// inserted no matter what order the module attributes are in.
+pub fn mark_tests(items: Vec<Item>, name: &str, attr: Attribute, test_attrs: &[Path]) -> Vec<Item> {
+    items
+        .into_iter()
+        .map(|t| match t {
+            Item::Fn(mut f) => {
+                if is_test(&f, test_attrs) && !f.attrs.iter().any(|a| is_attr(a, name)) {
+                    f.attrs.insert(0, attr.clone());
+                }
+                Item::Fn(f)
+            }
+            e => e,
+        })
+        .collect()
+}
+
+/// Parses the arguments of the `serial` and `max_concurrency` attributes, which are an optional
+/// limit followed by an optional group name, e.g. `(2, group = "db")`.
+pub fn limit_args(
+    args: AttributeArgs,
+    macro_name: &str,
+) -> syn::Result<(Option<LitInt>, Option<LitStr>)> {
+    let mut limit = None;
+    let mut group = None;
+    for arg in args {
+        match arg {
+            NestedMeta::Lit(Lit::Int(lit)) if limit.is_none() && group.is_none() => {
+                limit = Some(lit)
+            }
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(lit),
+                ..
+            })) if path.is_ident("group") && group.is_none() => group = Some(lit),
+            arg => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    format!(
+                        "Unexpected argument to the `{}` macro attribute. Expected something like `({}group = \"db\")`.",
+                        macro_name,
+                        if macro_name == "serial" { "" } else { "2, " }
+                    ),
+                ))
+            }
+        }
+    }
+    Ok((limit, group))
+}
+
+/// Marks a test in a hooked module so it is repeated `TEST_ENV_HELPERS_REPEAT` times and skipped if
+/// it doesn't match `TEST_TAGS`, unless it already has its own `repeat` and `tag` attributes. Tests
+/// with arguments aren't repeated because their body may move the arguments, which can't be done
+/// more than once. Also applies the test's own `before` and `after` functions, so they run inside
+/// the hook being applied.
+pub fn mark_hooked_test(f: &mut ItemFn) {
+    apply_test_hooks(f);
+    if f.sig.inputs.is_empty() && !f.attrs.iter().any(|attr| is_attr(attr, "repeat")) {
+        f.attrs
+            .insert(0, parse_quote!(#[::test_env_helpers::repeat]));
+    }
+    if !f.attrs.iter().any(|attr| is_attr(attr, "tag")) {
+        f.attrs.insert(0, parse_quote!(#[::test_env_helpers::tag]));
+    }
+}
+
+/// Appends the statements of `hook` to `block`. If `block` ends in an expression, like the `Ok(())`
+/// of a test returning a `Result`, its value is kept and returned after the hook has run.
+pub fn append_stmts(block: &mut Block, hook: &Block) {
+    match block.stmts.pop() {
+        Some(Stmt::Expr(tail)) => {
+            let value = Ident::new("value", Span::mixed_site());
+            block.stmts.push(parse_quote!(let #value = #tail;));
+            block.stmts.extend(hook.stmts.iter().cloned());
+            block.stmts.push(Stmt::Expr(parse_quote!(
+                #[allow(unreachable_code)]
+                #value
+            )));
+        }
+        last => {
+            block.stmts.extend(last);
+            block.stmts.extend(hook.stmts.iter().cloned());
+        }
+    }
+}
+
+/// Moves the functions named by the `before` and `after` attributes of the test `f` into its body,
+/// right around the test itself, in the order the attributes are declared. Whichever hook is
+/// applied to the test first does this, so the functions always run inside the module hooks.
+/// Attributes that can't be parsed are left alone to report their own error when expanded.
+pub fn apply_test_hooks(f: &mut ItemFn) {
+    let mut before = vec![];
+    let mut after = vec![];
+    f.attrs.retain(|attr| {
+        let hooks = if is_attr(attr, "before") {
+            &mut before
+        } else if is_attr(attr, "after") {
+            &mut after
+        } else {
+            return true;
+        };
+        match attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated) {
+            Ok(paths) => {
+                hooks.extend(paths);
+                false
+            }
+            Err(_) => true,
+        }
+    });
+    if before.is_empty() && after.is_empty() {
+        return;
+    }
+    let block = &f.block;
+    let value = Ident::new("value", Span::mixed_site());
+    let output_type = match &f.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let run = if after.is_empty() {
+        quote!(#block)
+    } else if f.sig.asyncness.is_some() {
+        quote! {
+            let #value: #output_type = async #block.await;
+            #(#after();)*
+            #value
+        }
+    } else {
+        quote! {
+            let #value = (|| -> #output_type #block)();
+            #(#after();)*
+            #value
+        }
+    };
+    *f.block = parse_quote!({
+        #(#before();)*
+        #run
+    });
+}
+
+/// Arguments accepted by all of the hook attributes, e.g. `#[before_each(tags = [db])]`.
+#[derive(Default)]
+pub struct HookArgs {
+    /// Only apply the hook to tests with at least one of these tags.
+    pub tags: Vec<Ident>,
+    /// Attributes, besides the built in ones, that mark a function as a test.
+    pub test_attrs: Vec<Path>,
+}
+
+impl Parse for HookArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = HookArgs::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let content;
+            bracketed!(content in input);
+            if key == "tags" {
+                args.tags
+                    .extend(Punctuated::<Ident, Token![,]>::parse_terminated(&content)?);
+            } else if key == "test_attrs" {
+                args.test_attrs
+                    .extend(Punctuated::<Path, Token![,]>::parse_terminated(&content)?);
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected `tags` or `test_attrs`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Takes the `test_attrs = [..]` argument out of the arguments of a module attribute with arguments
+/// of its own, e.g. `#[retry(3, test_attrs = [it])]`, returning the other arguments and the listed
+/// attributes, which mark a function as a test like they do for the hooks.
+pub fn take_test_attrs(args: TokenStream) -> syn::Result<(TokenStream, Vec<Path>)> {
+    let tokens: Vec<TokenTree> = args.into_iter().collect();
+    let mut rest = vec![];
+    let mut test_attrs = vec![];
+    let mut i = 0;
+    while let Some(token) = tokens.get(i) {
+        match &tokens[i..] {
+            [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Group(list), ..]
+                if key == "test_attrs"
+                    && eq.as_char() == '='
+                    && list.delimiter() == Delimiter::Bracket =>
+            {
+                test_attrs
+                    .extend(Punctuated::<Path, Token![,]>::parse_terminated.parse2(list.stream())?);
+                i += 3;
+                // Along with the comma separating it from the next argument, or the previous one.
+                match tokens.get(i) {
+                    Some(TokenTree::Punct(comma)) if comma.as_char() == ',' => i += 1,
+                    _ => {
+                        if matches!(rest.last(), Some(TokenTree::Punct(comma)) if comma.as_char() == ',')
+                        {
+                            rest.pop();
+                        }
+                    }
+                }
+            }
+            _ => {
+                rest.push(token.clone());
+                i += 1;
+            }
+        }
+    }
+    Ok((rest.into_iter().collect(), test_attrs))
+}
+
+/// Returns the tags listed in a `tag` attribute, i.e. `db` and `slow` in `#[tag(db, slow)]`.
+pub fn tag_names(tokens: TokenStream) -> syn::Result<Vec<String>> {
+    Ok(Punctuated::<Ident, Token![,]>::parse_terminated
+        .parse2(tokens)?
+        .into_iter()
+        .map(|tag| tag.to_string())
+        .collect())
+}
+
+/// Returns the tags of a test, which are the ones on the test itself and the ones on its module
+/// that haven't been applied to the test yet. Malformed `tag` attributes are ignored here, they
+/// report their own error when they are expanded.
+pub fn test_tags(f: &ItemFn, module_attrs: &[Attribute]) -> Vec<String> {
+    let mut tags: Vec<String> = f
+        .attrs
+        .iter()
+        .chain(module_attrs)
+        .filter(|attr| is_attr(attr, "tag") && !attr.tokens.is_empty())
+        .filter_map(|attr| {
+            attr.parse_args()
+                .ok()
+                .and_then(|tokens| take_test_attrs(tokens).ok())
+                .and_then(|(tokens, _)| tag_names(tokens).ok())
+        })
+        .flatten()
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Returns `true` if `hook` should be applied to `f`, which is the case for tests that haven't
+/// opted out of it and, if the hook is limited to some tags, have one of them.
+pub fn hook_applies(f: &ItemFn, hook: &str, args: &HookArgs, module_attrs: &[Attribute]) -> bool {
+    is_test(f, &args.test_attrs)
+        && !skips_hook(f, hook)
+        && (args.tags.is_empty()
+            || test_tags(f, module_attrs)
+                .iter()
+                .any(|tag| args.tags.iter().any(|hook_tag| hook_tag == tag)))
+}
+
+/// The statement that runs the body of `before_all` the first time any test of the module runs,
+/// along with the static it relies on.
+pub fn before_all_once(before_all: &Block) -> (Stmt, Item) {
+    let stmt = parse_quote! {
+        __TEST_ENV_HELPERS_BEFORE_ALL.call_once(|| {
+            #before_all
+        });
+    };
+    let once = parse_quote! {
+        static __TEST_ENV_HELPERS_BEFORE_ALL: ::std::sync::Once = ::std::sync::Once::new();
+    };
+    (stmt, once)
+}
+
+/// The entry of `f` in the `after_all` counter: how many tests it turns into, its tags, which
+/// decide whether it is selected by `TEST_TAGS`, and whether it is `#[ignore]`d.
+pub fn counted_test(f: &ItemFn, args: &HookArgs, module_attrs: &[Attribute]) -> TokenStream {
+    let test_count = test_count(f, &args.test_attrs);
+    let tags = test_tags(f, module_attrs);
+    let ignored = is_ignored(f);
+    quote!((#test_count, &[#(#tags),*], #ignored))
+}
+
+/// Returns `after_each`, the body of the hook, preceded by running the cleanups of the test, for
+/// inserting it after the body of a test that hasn't panicked.
+pub fn after_each_stmts(after_each: &Block) -> Block {
+    let mut block = after_each.clone();
+    block.stmts.insert(
+        0,
+        parse_quote! {
+            if let ::std::option::Option::Some(err) =
+                ::test_env_helpers::__private::run_cleanups(false)
+            {
+                ::std::panic::resume_unwind(err);
+            };
+        },
+    );
+    block
+}
+
+/// Statement that runs the cleanups of the test, whose caught result is `result`. A panicking
+/// cleanup replaces the result if the test hadn't failed yet. A panic the test was expected to
+/// raise counts as a failure here, so the cleanup can't replace it either.
+fn run_cleanups(result: &Ident) -> Stmt {
+    parse_quote! {
+        let #result = match ::test_env_helpers::__private::run_cleanups(
+            ::test_env_helpers::TestOutcome::of(
+                &#result,
+                ::test_env_helpers::__private::ShouldPanic::No,
+            )
+            .failed(),
+        ) {
+            ::std::option::Option::Some(err) => ::std::result::Result::Err(err),
+            ::std::option::Option::None => #result,
+        };
+    }
+}
+
+/// Wraps `block`, the body of the test `f`, so that the test is counted as finished even if it
+/// panics, and runs `after_all` once every test has finished. The panic is raised again afterwards.
+pub fn after_all_block(f: &ItemFn, block: &Block, after_all: &Block) -> Block {
+    let [result, value] = ["result", "value"].map(|name| Ident::new(name, Span::mixed_site()));
+    let output_type = match &f.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let catch_unwind: Stmt = if f.sig.asyncness.is_some() {
+        parse_quote! {
+            let #result = ::test_env_helpers::__private::CatchUnwind::new(
+                ::std::boxed::Box::pin(async {
+                    let #value: #output_type = async #block.await;
+                    #value
+                }),
+            )
+            .await;
+        }
+    } else {
+        parse_quote! {
+            let #result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                || -> #output_type #block,
+            ));
+        }
+    };
+    let summary = summary();
+    let run_cleanups = run_cleanups(&result);
+    let passed = passed();
+    let should_panic = should_panic(f);
+    parse_quote!({
+        __TEST_ENV_HELPERS_REMAINING_TESTS.start();
+        #catch_unwind
+        #run_cleanups
+        let #passed = ::test_env_helpers::TestOutcome::of(&#result, #should_panic).passed();
+        __TEST_ENV_HELPERS_REMAINING_TESTS.finish(
+            ::test_env_helpers::__private::is_final_run(#passed),
+            #passed,
+            |#summary| #after_all,
+        );
+        match #result {
+            ::std::result::Result::Ok(#value) => #value,
+            ::std::result::Result::Err(err) => ::std::panic::resume_unwind(err),
+        }
+    })
+}
+
+/// The static that [`after_all_block`] relies on, where `tests` are the [`counted_test`]s of the
+/// module.
+pub fn after_all_statics(tests: &[TokenStream]) -> Vec<Item> {
+    vec![parse_quote! {
+        static __TEST_ENV_HELPERS_REMAINING_TESTS: ::test_env_helpers::__private::TestCounter =
+            ::test_env_helpers::__private::TestCounter::new(module_path!(), &[#(#tests),*]);
+    }]
+}
+
+/// How a test calls hooks whose bodies aren't inserted into it, see [`called_hooks_block`]. Each
+/// call is a statement, except for `counter`, which is an expression for the `TestCounter` of the
+/// `after_all` hook, if there is one. `after_each` can use [`outcome`].
+#[derive(Default)]
+pub struct HookCalls {
+    pub counter: Option<TokenStream>,
+    pub before_all: Option<TokenStream>,
+    pub before_each: Option<TokenStream>,
+    pub after_each: Option<TokenStream>,
+    /// Runs once the test has finished, with `final_run()` telling whether it won't be rerun and
+    /// `passed()` whether it passed.
+    pub finish: Option<TokenStream>,
+}
+
+/// The variable [`HookCalls::finish`] can use to tell whether the test won't be rerun.
+pub fn final_run() -> Ident {
+    Ident::new("final_run", Span::mixed_site())
+}
+
+/// The variable [`HookCalls::finish`] can use to tell whether the test passed.
+pub fn passed() -> Ident {
+    Ident::new("passed", Span::mixed_site())
+}
+
+/// Wraps the body of the test `f` so it calls the hooks in `calls`, and registers the test so the
+/// `after_all` hook knows when every test has finished. The `before_each` and `after_each` calls
+/// are left out if the test opts out of them.
+pub fn called_hooks_block(f: &ItemFn, calls: HookCalls) -> Block {
+    let HookCalls {
+        counter,
+        before_all,
+        before_each,
+        after_each,
+        finish,
+    } = calls;
+    let before_each = before_each.filter(|_| !skips_hook(f, "before_each"));
+    let after_each = after_each.filter(|_| !skips_hook(f, "after_each"));
+    let register = counter.map(|counter| {
+        let count = match is_skipped(f) {
+            true => 0,
+            false => test_count(f, &[]).max(1),
+        };
+        let tags = test_tags(f, &[]);
+        let ignored = is_ignored(f);
+        quote! {
+            ::test_env_helpers::__private::submit! {
+                ::test_env_helpers::__private::RegisteredTest::new(
+                    #counter,
+                    #count,
+                    &[#(#tags),*],
+                    #ignored,
+                )
+            }
+            (#counter).start();
+        }
+    });
+    let block = &f.block;
+    let [result, value] = ["result", "value"].map(|name| Ident::new(name, Span::mixed_site()));
+    let [final_run, passed] = [final_run(), passed()];
+    let output_type = match &f.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let run: Stmt = if f.sig.asyncness.is_some() {
+        parse_quote! {
+            let #result = ::test_env_helpers::__private::CatchUnwind::new(::std::boxed::Box::pin(
+                async {
+                    #before_all
+                    #before_each
+                    let #value: #output_type = async #block.await;
+                    #value
+                },
+            ))
+            .await;
+        }
+    } else {
+        parse_quote! {
+            let #result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                || -> #output_type {
+                    #before_all
+                    #before_each
+                    (|| -> #output_type #block)()
+                },
+            ));
+        }
+    };
+    // `after_each` gets the outcome of the test, so it is run outside of it. If it panics, the test
+    // fails with that panic, unless it had already failed.
+    let should_panic = should_panic(f);
+    let after_each = after_each.map(|after_each| {
+        let outcome = outcome();
+        quote! {
+            let #result = {
+                let #outcome = &::test_env_helpers::TestOutcome::of(&#result, #should_panic);
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                    #after_each
+                })) {
+                    ::std::result::Result::Err(err) if #result.is_ok() => {
+                        ::std::result::Result::Err(err)
+                    }
+                    _ => #result,
+                }
+            };
+        }
+    });
+    let run_cleanups = run_cleanups(&result);
+    parse_quote!({
+        #register
+        #run
+        #run_cleanups
+        #after_each
+        let #passed = ::test_env_helpers::TestOutcome::of(&#result, #should_panic).passed();
+        let #final_run = ::test_env_helpers::__private::is_final_run(#passed);
+        #finish
+        match #result {
+            ::std::result::Result::Ok(#value) => #value,
+            ::std::result::Result::Err(err) => ::std::panic::resume_unwind(err),
+        }
+    })
+}
+
+/// Wraps the body of the test `f` so it runs the hooks declared by the `#[hooks]` mod at `path`.
+pub fn with_hooks_block(f: &ItemFn, path: &Path) -> Block {
+    let hooks = quote!(#path::__TEST_ENV_HELPERS_HOOKS);
+    let [final_run, passed, outcome] = [final_run(), passed(), outcome()];
+    let result = Ident::new("result", Span::mixed_site());
+    let guard = Ident::new("_guard", Span::mixed_site());
+    called_hooks_block(
+        f,
+        HookCalls {
+            counter: Some(quote!(#hooks.counter())),
+            before_all: Some(quote!(#hooks.before_all();)),
+            before_each: Some(quote!(let #guard = #hooks.before_each();)),
+            after_each: Some(quote!(#hooks.after_each(#outcome, #result.is_err());)),
+            finish: Some(quote!(#hooks.finish_one(#final_run, #passed);)),
+        },
+    )
+}
+
+/// Adds `hook` to the `hooked` attributes in the `macro_rules!` definitions among `items`, so the
+/// tests they generate run it too, limited to the tags in `args`. Returns `true` if there were any,
+/// in which case the module has to define the [`hooked_fn`] those tests call.
+pub fn mark_hooked_macros(items: &mut [Item], hook: &str, args: &HookArgs) -> bool {
+    let hook = Ident::new(hook, Span::call_site());
+    let tags = &args.tags;
+    let hook = if tags.is_empty() {
+        quote!(#hook)
+    } else {
+        quote!(#hook = [#(#tags),*])
+    };
+    let mut found = false;
+    for item in items {
+        if let Item::Macro(m) = item {
+            if m.mac.path.is_ident("macro_rules") {
+                m.mac.tokens = mark_hooked_attrs(m.mac.tokens.clone(), &hook, &mut found);
+            }
+        }
+    }
+    found
+}
+
+/// Rewrites every `#[hooked]` or `#[hooked(..)]` in `tokens` to a `hooked` attribute that also
+/// includes `hook`, setting `found` if there was one.
+fn mark_hooked_attrs(tokens: TokenStream, hook: &TokenStream, found: &mut bool) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for i in 0..tokens.len() {
+        let TokenTree::Group(group) = &tokens[i] else {
+            continue;
+        };
+        let is_attr = i > 0 && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '#');
+        let stream = match hooked_args(group) {
+            Some(args) if is_attr => {
+                *found = true;
+                let separator = (!args.is_empty()).then(|| quote!(,));
+                quote!(::test_env_helpers::hooked(#args #separator #hook))
+            }
+            _ => mark_hooked_attrs(group.stream(), hook, found),
+        };
+        let mut marked = Group::new(group.delimiter(), stream);
+        marked.set_span(group.span());
+        tokens[i] = TokenTree::Group(marked);
+    }
+    tokens.into_iter().collect()
+}
+
+/// Returns the arguments of `group` if it is the inside of a `hooked` attribute, like `[hooked]` or
+/// `[::test_env_helpers::hooked(before_each)]`.
+fn hooked_args(group: &Group) -> Option<TokenStream> {
+    if group.delimiter() != Delimiter::Bracket {
+        return None;
+    }
+    let mut tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    let args = match tokens.last() {
+        Some(TokenTree::Group(args)) if args.delimiter() == Delimiter::Parenthesis => {
+            let args = args.stream();
+            tokens.pop();
+            args
+        }
+        _ => TokenStream::new(),
+    };
+    let is_path = tokens.iter().all(|token| match token {
+        TokenTree::Ident(_) => true,
+        TokenTree::Punct(p) => p.as_char() == ':',
+        _ => false,
+    });
+    match tokens.last() {
+        Some(TokenTree::Ident(name)) if is_path && name == "hooked" => Some(args),
+        _ => None,
+    }
+}
+
+/// The name of the function that tests generated by macros call to run `hook`.
+fn hooked_fn_name(hook: &str) -> Ident {
+    format_ident!("__test_env_helpers_{}", hook)
+}
+
+/// The function that tests generated by macros call to run `hook`, whose body is `block` and
+/// signature is `sig`.
+pub fn hooked_fn(hook: &str, block: &Block, sig: &Signature) -> Item {
+    let mut f = called_hook_fn(hooked_fn_name(hook), hook, block, sig);
+    f.attrs.push(parse_quote!(#[allow(dead_code)]));
+    Item::Fn(f)
+}
+
+/// A function called `name` that runs `hook`, whose body is `block` and signature is `sig`, for
+/// hooks that are called rather than inserted into the tests. `before_each` returns its guard
+/// boxed, so the test can hold it whatever its type. `after_each` is passed the outcome of the test
+/// and returns early for tests that panicked, even if they were expected to, unless it takes the
+/// outcome itself. `after_all` is passed the summary of the mod.
+pub fn called_hook_fn(name: Ident, hook: &str, block: &Block, sig: &Signature) -> ItemFn {
+    match hook {
+        "before_each" => {
+            let output = &sig.output;
+            parse_quote! {
+                fn #name() -> ::std::boxed::Box<dyn ::test_env_helpers::__private::Guard> {
+                    fn before_each() #output #block
+                    ::std::boxed::Box::new(before_each())
+                }
+            }
+        }
+        "after_each" => {
+            let [outcome, panicked] = [outcome(), Ident::new("panicked", Span::mixed_site())];
+            let skip_panicked = (!takes_outcome(sig)).then(|| {
+                quote! {
+                    if #panicked {
+                        return;
+                    }
+                }
+            });
+            parse_quote! {
+                fn #name(#outcome: &::test_env_helpers::TestOutcome, #panicked: bool) {
+                    #skip_panicked
+                    #block
+                }
+            }
+        }
+        "after_all" => {
+            let summary = summary();
+            parse_quote!(fn #name(#summary: &::test_env_helpers::ModuleSummary) #block)
+        }
+        _ => parse_quote!(fn #name() #block),
+    }
+}
+
+/// Wraps `block`, the body of the test `f`, so `after_each`, which takes the outcome of the test,
+/// runs after it whether it passed or not. The panic of a failed test is raised again afterwards.
+pub fn outcome_block(f: &ItemFn, block: &Block, after_each: &Block) -> Block {
+    let [result, value] = ["result", "value"].map(|name| Ident::new(name, Span::mixed_site()));
+    let outcome = outcome();
+    let output_type = match &f.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let catch_unwind: Stmt = if f.sig.asyncness.is_some() {
+        parse_quote! {
+            let #result = ::test_env_helpers::__private::CatchUnwind::new(::std::boxed::Box::pin(
+                async {
+                    let #value: #output_type = async #block.await;
+                    #value
+                },
+            ))
+            .await;
+        }
+    } else {
+        parse_quote! {
+            let #result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                || -> #output_type #block,
+            ));
+        }
+    };
+    let run_cleanups = run_cleanups(&result);
+    let should_panic = should_panic(f);
+    parse_quote!({
+        #catch_unwind
+        #run_cleanups
+        let #outcome = &::test_env_helpers::TestOutcome::of(&#result, #should_panic);
+        #after_each
+        match #result {
+            ::std::result::Result::Ok(#value) => #value,
+            ::std::result::Result::Err(err) => ::std::panic::resume_unwind(err),
+        }
+    })
+}
+
+/// A hook listed in a `hooked` attribute, e.g. `before_each` or `after_each = [db]`.
+pub struct HookedArg {
+    pub hook: Ident,
+    /// Only run the hook if the test has at least one of these tags.
+    pub tags: Vec<Ident>,
+}
+
+impl Parse for HookedArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let hook: Ident = input.parse()?;
+        if !HOOKS.iter().any(|name| hook == name) {
+            return Err(syn::Error::new(
+                hook.span(),
+                "expected `before_all`, `before_each`, `after_each` or `after_all`",
+            ));
+        }
+        let mut tags = vec![];
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            let content;
+            bracketed!(content in input);
+            tags.extend(Punctuated::<Ident, Token![,]>::parse_terminated(&content)?);
+        }
+        Ok(HookedArg { hook, tags })
+    }
+}
+
+/// Wraps the body of the test `f`, which was generated by a macro, so it calls the [`hooked_fn`]s
+/// of its module for the `hooks` that apply to it.
+pub fn hooked_block(f: &ItemFn, hooks: &[HookedArg]) -> Block {
+    let tags = test_tags(f, &[]);
+    let mut calls = HookCalls::default();
+    for HookedArg {
+        hook,
+        tags: hook_tags,
+    } in hooks
+    {
+        if !hook_tags.is_empty()
+            && !hook_tags
+                .iter()
+                .any(|hook_tag| tags.contains(&hook_tag.to_string()))
+        {
+            continue;
+        }
+        let hook = hook.to_string();
+        let name = hooked_fn_name(&hook);
+        match hook.as_str() {
+            "before_all" => {
+                calls.before_all = Some(quote!(__TEST_ENV_HELPERS_BEFORE_ALL.call_once(#name);))
+            }
+            "before_each" => {
+                let guard = Ident::new("_guard", Span::mixed_site());
+                calls.before_each = Some(quote!(let #guard = #name();))
+            }
+            "after_each" => {
+                let outcome = outcome();
+                let result = Ident::new("result", Span::mixed_site());
+                calls.after_each = Some(quote!(#name(#outcome, #result.is_err());))
+            }
+            _ => {
+                let [final_run, passed] = [final_run(), passed()];
+                calls.counter = Some(quote!(&__TEST_ENV_HELPERS_REMAINING_TESTS));
+                calls.finish = Some(quote! {
+                    __TEST_ENV_HELPERS_REMAINING_TESTS.finish(#final_run, #passed, #name);
+                });
+            }
+        }
+    }
+    called_hooks_block(f, calls)
+}
+
+/// Wraps the body of a test in a loop that runs it until `driver` says to stop. `driver` is an
+/// expression creating one of the runtime `Retry` or `Repeat` types. Async tests are wrapped in an
+/// async loop so panics are caught across `.await` points.
+pub fn rerun_block(f: &ItemFn, driver: TokenStream) -> Block {
+    let block = &f.block;
+    let output = &f.sig.output;
+    // Mixed site spans keep these from shadowing anything the test body refers to.
+    let [driver_var, future, result, value] =
+        ["driver", "future", "result", "value"].map(|name| Ident::new(name, Span::mixed_site()));
+    if f.sig.asyncness.is_some() {
+        let output_type = match output {
+            ReturnType::Default => quote!(()),
+            ReturnType::Type(_, ty) => quote!(#ty),
+        };
+        parse_quote!({
+            let mut #driver_var = #driver;
+            loop {
+                #driver_var.begin();
+                let #future: ::std::pin::Pin<
+                    ::std::boxed::Box<dyn ::std::future::Future<Output = #output_type>>,
+                > = ::std::boxed::Box::pin(async #block);
+                let #result = ::test_env_helpers::__private::CatchUnwind::new(#future).await;
+                if let ::std::option::Option::Some(#value) = #driver_var.end(#result) {
+                    break #value;
+                }
+            }
+        })
+    } else {
+        parse_quote!({
+            let mut #driver_var = #driver;
+            loop {
+                #driver_var.begin();
+                let #result =
+                    ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #output #block));
+                if let ::std::option::Option::Some(#value) = #driver_var.end(#result) {
+                    break #value;
+                }
+            }
+        })
+    }
+}
+
+/// Wraps the body of `f` so it is the current test until its body has finished, after which the
+/// remaining cleanups run and the outcome is reported. `tags` are all of its tags, and `prelude`
+/// builds the code that runs before the body from the variable holding the scope.
+pub fn test_scope(
+    f: &ItemFn,
+    tags: &[String],
+    prelude: impl FnOnce(&Ident) -> TokenStream,
+) -> Block {
+    let name = f.sig.ident.to_string();
+    let args = f.sig.inputs.iter().map(|input| match input {
+        FnArg::Typed(PatType { pat, .. }) => match &**pat {
+            Pat::Ident(arg) => {
+                let arg = &arg.ident;
+                quote! {{
+                    use ::test_env_helpers::__private::{DebugArg as _, OpaqueArg as _};
+                    (&&::test_env_helpers::__private::Arg(&#arg)).describe()
+                }}
+            }
+            _ => quote!(::std::string::String::from("_")),
+        },
+        FnArg::Receiver(_) => quote!(::std::string::String::from("self")),
+    });
+    let [scope, result, value] =
+        ["scope", "result", "value"].map(|name| Ident::new(name, Span::mixed_site()));
+    let prelude = prelude(&scope);
+    let should_panic = should_panic(f);
+    let block = &f.block;
+    let output_type = match &f.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let run = if f.sig.asyncness.is_some() {
+        quote! {
+            ::test_env_helpers::__private::CatchUnwind::new(::std::boxed::Box::pin(
+                async { async #block.await },
+            ))
+            .await
+        }
+    } else {
+        quote!(::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| -> #output_type #block)))
+    };
+    parse_quote!({
+        let #scope = ::test_env_helpers::__private::TestScope::enter(
+            ::test_env_helpers::TestInfo::new(
+                #name,
+                module_path!(),
+                &[#(#tags),*],
+                ::std::vec![#(#args),*],
+                #should_panic,
+            ),
+        );
+        #prelude
+        let #result: ::std::thread::Result<#output_type> = #run;
+        match #scope.finish(#result) {
+            ::std::result::Result::Ok(#value) => #value,
+            ::std::result::Result::Err(err) => ::std::panic::resume_unwind(err),
+        }
+    })
+}
+
+/// The `ShouldPanic` of the test `f`, which `TestOutcome` needs to tell whether it passed.
+pub fn should_panic(f: &ItemFn) -> TokenStream {
+    let Some(attr) = f
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("should_panic"))
+    else {
+        return quote!(::test_env_helpers::__private::ShouldPanic::No);
+    };
+    let expected = match attr.parse_meta() {
+        Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(expected),
+            ..
+        })) => Some(expected),
+        Ok(Meta::List(list)) => list.nested.into_iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(expected),
+                ..
+            })) if path.is_ident("expected") => Some(expected),
+            _ => None,
+        }),
+        _ => None,
+    };
+    match expected {
+        Some(expected) => {
+            quote!(::test_env_helpers::__private::ShouldPanic::YesWithMessage(#expected))
+        }
+        None => quote!(::test_env_helpers::__private::ShouldPanic::Yes),
+    }
+}
+
+/// The name generated code reports for a test, e.g. `my_crate::my_tests::test_1`.
+pub fn test_name(f: &ItemFn) -> TokenStream {
+    let name = f.sig.ident.to_string();
+    quote!(concat!(module_path!(), "::", #name))
+}
+
+/// Registers the tests in `item`, which `skip` removes, so reports can still list them as skipped.
+/// `test_attrs` are the attributes marking tests besides the built in ones.
+pub fn skipped_tests(item: &Item, test_attrs: &[Path]) -> TokenStream {
+    let mut registrations = TokenStream::new();
+    collect_skipped_tests(
+        item,
+        test_attrs,
+        &mut vec![],
+        &mut vec![],
+        &mut registrations,
+    );
+    registrations
+}
+
+/// Registers the tests in `item`, which is nested in the modules `path` below the one `skip` is
+/// applied in. `cfgs` are the `cfg` attributes of those modules, so tests that aren't compiled
+/// aren't registered either.
+fn collect_skipped_tests<'a>(
+    item: &'a Item,
+    test_attrs: &[Path],
+    path: &mut Vec<String>,
+    cfgs: &mut Vec<&'a Attribute>,
+    registrations: &mut TokenStream,
+) {
+    let (attrs, ident) = match item {
+        Item::Fn(f) if is_test(f, test_attrs) => (&f.attrs, &f.sig.ident),
+        Item::Mod(m) => (&m.attrs, &m.ident),
+        _ => return,
+    };
+    let outer = cfgs.len();
+    cfgs.extend(attrs.iter().filter(|attr| attr.path.is_ident("cfg")));
+    match item {
+        Item::Mod(m) => {
+            path.push(ident.to_string());
+            for item in m.content.iter().flat_map(|(_, items)| items) {
+                collect_skipped_tests(item, test_attrs, path, cfgs, registrations);
+            }
+            path.pop();
+        }
+        _ => {
+            let name = ident.to_string();
+            registrations.extend(quote! {
+                #(#cfgs)*
+                ::test_env_helpers::__private::submit! {
+                    ::test_env_helpers::__private::SkippedTest::new(
+                        concat!(module_path!() #(, "::", #path)*),
+                        #name,
+                    )
+                }
+            });
+        }
+    }
+    cfgs.truncate(outer);
+}
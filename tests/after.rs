@@ -1,3 +1,5 @@
+mod common;
+
 use test_env_helpers::*;
 
 #[after_all]
@@ -7,11 +9,11 @@ mod after_all {
     use std::thread;
     use std::time::Duration;
     use test_case::test_case;
-    use tokio;
 
     static T: AtomicUsize = AtomicUsize::new(0);
     fn after_all() {
         assert_eq!(T.load(Ordering::SeqCst), 10);
+        crate::common::after_all_ran(module_path!());
     }
 
     #[test]
@@ -45,7 +47,6 @@ mod after_each {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Mutex;
     use test_case::test_case;
-    use tokio;
 
     lazy_static! {
         static ref MTX: Mutex<()> = Mutex::new(());
@@ -74,3 +75,8 @@ mod after_each {
         assert_eq!(T.load(Ordering::SeqCst), 3);
     }
 }
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["after::after_all"]);
+}
@@ -5,7 +5,6 @@ use test_env_helpers::*;
 mod before_all {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use test_case::test_case;
-    use tokio;
 
     static T: AtomicUsize = AtomicUsize::new(0);
     fn before_all() {
@@ -31,7 +30,6 @@ mod before_all {
 mod before_each {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use test_case::test_case;
-    use tokio;
 
     static T: AtomicUsize = AtomicUsize::new(0);
     fn before_each() {
@@ -0,0 +1,114 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_each]
+#[after_each]
+#[after_all]
+#[cfg(test)]
+mod with_module_hooks {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+    }
+    static AFTER_EACH_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+    fn events() -> Vec<&'static str> {
+        EVENTS.with(|events| events.borrow().clone())
+    }
+    fn seed_users() {
+        record("seed_users");
+    }
+    fn seed_posts() {
+        record("seed_posts");
+    }
+    fn drop_users() {
+        record("drop_users");
+    }
+    fn panic_in_before() {
+        panic!("setup failed");
+    }
+
+    fn before_each() {
+        EVENTS.with(|events| events.borrow_mut().clear());
+        record("before_each");
+    }
+    fn after_each() {
+        record("after_each");
+        AFTER_EACH_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        // Runs even though `failing_before` panicked in its `before` function.
+        assert_eq!(AFTER_EACH_RUNS.load(Ordering::SeqCst), 3);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[before(seed_users, seed_posts)]
+    #[after(drop_users)]
+    #[test]
+    fn both() {
+        assert_eq!(events(), ["before_each", "seed_users", "seed_posts"]);
+    }
+    #[test]
+    #[after(drop_users)]
+    fn after_only() {
+        record("test");
+    }
+    #[before(seed_users)]
+    #[tokio::test]
+    async fn async_test() {
+        tokio::task::yield_now().await;
+        assert_eq!(events(), ["before_each", "seed_users"]);
+    }
+    #[before(panic_in_before)]
+    #[test]
+    #[should_panic = "setup failed"]
+    fn failing_before() {}
+}
+
+#[cfg(test)]
+mod without_module_hooks {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+    }
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+    fn seed() {
+        record("seed");
+    }
+    fn check() {
+        assert_eq!(
+            EVENTS.with(|events| events.borrow().clone()),
+            ["seed", "test"]
+        );
+    }
+
+    #[before(seed)]
+    #[after(check)]
+    #[test]
+    fn sync_test() {
+        record("test");
+    }
+    #[after(check)]
+    #[before(seed)]
+    #[test]
+    fn result_test() -> Result<(), String> {
+        record("test");
+        Ok(())
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["before_after::with_module_hooks"]);
+}
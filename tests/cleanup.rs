@@ -0,0 +1,128 @@
+use test_env_helpers::*;
+
+#[cfg(test)]
+mod events {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+    }
+
+    pub fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+    pub fn take() -> Vec<&'static str> {
+        EVENTS.with(|events| events.take())
+    }
+}
+
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod inserted_hooks {
+    use super::events::{record, take};
+    use super::*;
+
+    fn before_each() {
+        take();
+        cleanup(|| record("before_each"));
+    }
+    fn after_each(info: &TestInfo, outcome: &TestOutcome) {
+        // The tests that panic are expected to, so they pass as well.
+        assert_eq!(outcome, &TestOutcome::Passed);
+        let expected: &[&str] = match info.should_panic() {
+            false => &["second", "first", "before_each"],
+            true => &["after_panic", "before_each"],
+        };
+        assert_eq!(take(), expected);
+    }
+
+    #[test]
+    fn runs_in_reverse_order() {
+        cleanup(|| record("first"));
+        cleanup(|| record("second"));
+        assert!(take().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "original")]
+    fn runs_after_a_panic() {
+        cleanup(|| record("after_panic"));
+        panic!("original");
+    }
+
+    #[test]
+    #[should_panic(expected = "original")]
+    fn doesnt_hide_the_original_panic() {
+        cleanup(|| record("after_panic"));
+        cleanup(|| panic!("cleanup"));
+        panic!("original");
+    }
+
+    #[test]
+    #[should_panic(expected = "cleanup")]
+    fn fails_a_passing_test() {
+        cleanup(|| record("after_panic"));
+        cleanup(|| panic!("cleanup"));
+    }
+
+    #[tokio::test]
+    async fn async_test() {
+        cleanup(|| record("first"));
+        tokio::task::yield_now().await;
+        cleanup(|| record("second"));
+    }
+}
+
+#[after_each]
+#[cfg(test)]
+mod after_each_without_outcome {
+    use super::events::{record, take};
+    use super::*;
+
+    fn after_each() {
+        assert_eq!(take(), ["cleanup"]);
+    }
+
+    #[test]
+    fn runs_before_after_each() {
+        take();
+        cleanup(|| record("cleanup"));
+    }
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use super::events::take;
+
+    fn after_each() {
+        assert_eq!(take(), ["cleanup"]);
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::events::{record, take};
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    fn runs_before_shared_after_each() {
+        take();
+        cleanup(|| record("cleanup"));
+    }
+
+    #[tag(untouched)]
+    #[test]
+    #[should_panic(expected = "cleanup")]
+    fn runs_at_the_end_of_a_tagged_test() {
+        cleanup(|| panic!("cleanup"));
+    }
+
+    #[test]
+    #[should_panic(expected = "`cleanup` can only be called")]
+    fn panics_outside_of_a_hooked_test() {
+        cleanup(|| {});
+    }
+}
@@ -0,0 +1,98 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// A command running this test binary again with the env var `var` set to `value`, for tests of
+/// features configured through the environment. Returns `None` when this is already that run, in
+/// which case the test starting it should return right away.
+pub fn rerun(var: &str, value: impl AsRef<OsStr>) -> Option<Command> {
+    if env::var_os(var).is_some() {
+        return None;
+    }
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.env(var, value);
+    Some(command)
+}
+
+/// Reports that the `after_all` hook of the module at `module_path` ran, for
+/// [`assert_after_all_ran`] to find.
+pub fn after_all_ran(module_path: &str) {
+    println!("after_all ran: {}", module_path);
+}
+
+/// Runs this test binary again and asserts that its tests pass and that the `after_all` hooks of
+/// the modules at `module_paths` ran, which they report with [`after_all_ran`]. Asserting in
+/// `after_all` alone checks nothing if the hook never runs, e.g. because its tests are miscounted.
+pub fn assert_after_all_ran(module_paths: &[&str]) {
+    let Some(mut rerun) = rerun("TEST_ENV_HELPERS_AFTER_ALL_CHECK", "1") else {
+        return;
+    };
+    let output = rerun.arg("--nocapture").output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    for path in module_paths {
+        assert!(
+            stdout.contains(&format!("after_all ran: {}\n", path)),
+            "`after_all` of `{}` didn't run",
+            path
+        );
+    }
+}
+
+/// Declares the `reported` mod for the tests of a reporter, which [`rerun_reported`] runs again
+/// with the reporter's env var `$var` set. In that run `fails_in_before_each` fails in its
+/// `before_each` hook with the message `$setup_failure`, and the tests in `$items` can check
+/// `reporting()` to fail too. `skipped_by_tag` is left out by its `slow` tag.
+#[macro_export]
+macro_rules! reported_tests {
+    ($var:literal, $setup_failure:literal, $($item:item)*) => {
+        #[test_env_helpers::before_each]
+        #[test_env_helpers::after_each]
+        #[cfg(test)]
+        mod reported {
+            use test_env_helpers::*;
+
+            fn reporting() -> bool {
+                std::env::var_os($var).is_some()
+            }
+
+            fn before_each(info: &TestInfo) {
+                if reporting() && info.name() == "fails_in_before_each" {
+                    panic!($setup_failure);
+                }
+            }
+            fn after_each() {}
+
+            #[test]
+            fn passing() {}
+
+            #[test]
+            fn fails_in_before_each() {}
+
+            #[test]
+            #[should_panic(expected = "expected")]
+            fn expected_panic() {
+                assert_eq!(0, 1, "expected");
+            }
+
+            #[tag(slow)]
+            #[test]
+            fn skipped_by_tag() {}
+
+            $($item)*
+        }
+    };
+}
+
+/// A command running the tests of the mod declared with [`reported_tests!`] again with `var` set
+/// to `value`, on two threads and leaving out the ones tagged `slow`. Returns `None` when this is
+/// already that run.
+pub fn rerun_reported(var: &str, value: impl AsRef<OsStr>) -> Option<Command> {
+    let mut command = rerun(var, value)?;
+    command
+        .args(["reported", "--test-threads=2"])
+        .env("TEST_TAGS", "!slow");
+    Some(command)
+}
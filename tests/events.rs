@@ -0,0 +1,80 @@
+mod common;
+
+reported_tests! {
+    "TEST_ENV_HELPERS_EVENTS",
+    "setup failed",
+
+    #[tag(db)]
+    #[test]
+    fn tagged() {}
+
+    #[test]
+    fn returns_err() -> Result<(), String> {
+        match reporting() {
+            true => Err("broken".to_string()),
+            false => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn events_env() {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    let path = env::temp_dir().join(format!("test-env-helpers-events-{}.jsonl", process::id()));
+    let Some(mut rerun) = common::rerun_reported("TEST_ENV_HELPERS_EVENTS", &path) else {
+        return;
+    };
+    let _ = fs::remove_file(&path);
+    let output = rerun.output().unwrap();
+    assert!(!output.status.success());
+    let events = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = events.lines().collect();
+    assert!(lines
+        .iter()
+        .all(|line| line.starts_with("{\"event\":\"") && line.ends_with('}')));
+    let find = |prefix: &str| {
+        lines
+            .iter()
+            .find(|line| line.starts_with(prefix))
+            .unwrap_or_else(|| panic!("no event starting with {}", prefix))
+            .to_string()
+    };
+
+    let started = find(r#"{"event":"test_started","test":"events::reported::passing""#);
+    assert!(started
+        .contains(r#""module_path":"events::reported","name":"passing","tags":[],"args":[]"#));
+    assert!(started.contains(r#""pid":"#));
+    let started = find(r#"{"event":"test_started","test":"events::reported::tagged""#);
+    assert!(started.contains(r#""name":"tagged","tags":["db"]"#));
+    find(r#"{"event":"hook_started","hook":"before_each","test":"events::reported::passing""#);
+    let hook =
+        find(r#"{"event":"hook_finished","hook":"after_each","test":"events::reported::passing""#);
+    assert!(hook.contains(r#""outcome":"passed","duration_ms":"#));
+    let passed = find(r#"{"event":"test_finished","test":"events::reported::passing""#);
+    assert!(passed.contains(r#""outcome":"passed","duration_ms":"#));
+    assert!(!passed.contains("failed_hook"));
+
+    let hook = find(
+        r#"{"event":"hook_finished","hook":"before_each","test":"events::reported::fails_in_before_each""#,
+    );
+    assert!(hook.contains(r#""outcome":"panicked","message":"setup failed""#));
+    let failed =
+        find(r#"{"event":"test_finished","test":"events::reported::fails_in_before_each""#);
+    assert!(failed.contains(r#""outcome":"panicked","message":"setup failed""#));
+    assert!(failed.contains(r#""failed_hook":"before_each""#));
+
+    let failed = find(r#"{"event":"test_finished","test":"events::reported::returns_err""#);
+    assert!(failed.contains(r#""outcome":"failed","message":"\"broken\"""#));
+    assert!(!failed.contains("failed_hook"));
+
+    let passed = find(r#"{"event":"test_finished","test":"events::reported::expected_panic""#);
+    assert!(passed.contains(r#""outcome":"passed","duration_ms":"#));
+
+    let skipped = find(r#"{"event":"test_finished","test":"events::reported::skipped_by_tag""#);
+    assert!(skipped.contains(r#""tags":["slow"],"outcome":"skipped""#));
+}
@@ -0,0 +1,200 @@
+use test_env_helpers::*;
+
+#[cfg(test)]
+mod lock {
+    use std::cell::Cell;
+    use std::sync::{Mutex, MutexGuard, TryLockError};
+
+    thread_local! {
+        static RELEASED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Holds the lock of a mod and counts how often it was released on this thread.
+    pub struct Held {
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for Held {
+        fn drop(&mut self) {
+            RELEASED.with(|released| released.set(released.get() + 1));
+        }
+    }
+
+    pub fn hold(lock: &'static Mutex<()>) -> Held {
+        Held {
+            _lock: lock.lock().unwrap_or_else(|err| err.into_inner()),
+        }
+    }
+
+    pub fn is_held(lock: &Mutex<()>) -> bool {
+        matches!(lock.try_lock(), Err(TryLockError::WouldBlock))
+    }
+
+    /// Runs `test` on this thread, which checks that its guard is held, and returns how many
+    /// guards were released by the time it returned.
+    pub fn released_by(test: fn()) -> usize {
+        let before = RELEASED.with(Cell::get);
+        test();
+        RELEASED.with(Cell::get) - before
+    }
+}
+
+#[before_each]
+#[cfg(test)]
+mod inserted_hooks {
+    use super::lock::{hold, is_held, released_by, Held};
+    use super::*;
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn before_each() -> Held {
+        hold(&LOCK)
+    }
+
+    #[test]
+    fn holds_the_guard() {
+        assert!(is_held(&LOCK));
+    }
+
+    #[tokio::test]
+    async fn holds_the_guard_across_awaits() {
+        tokio::task::yield_now().await;
+        assert!(is_held(&LOCK));
+    }
+
+    macro_rules! generated_test {
+        ($name:ident) => {
+            #[hooked]
+            #[test]
+            fn $name() {
+                assert!(is_held(&LOCK));
+            }
+        };
+    }
+    generated_test!(generated);
+
+    #[no_hooks]
+    #[test]
+    fn drops_the_guards_when_the_tests_finish() {
+        assert_eq!(released_by(holds_the_guard), 1);
+        assert_eq!(released_by(holds_the_guard_across_awaits), 1);
+        assert_eq!(released_by(generated), 1);
+    }
+}
+
+#[before_each]
+#[cfg(test)]
+mod opaque_guard {
+    use super::lock::{hold, is_held, released_by};
+    use super::*;
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn before_each() -> impl Drop {
+        // Only returns from the hook, not from the tests it is inserted into.
+        if !LOCK.is_poisoned() {
+            return hold(&LOCK);
+        }
+        hold(&LOCK)
+    }
+
+    #[test]
+    fn holds_the_guard() {
+        assert!(is_held(&LOCK));
+    }
+
+    #[no_hooks]
+    #[test]
+    fn drops_the_guard_when_the_test_finishes() {
+        assert_eq!(released_by(holds_the_guard), 1);
+    }
+}
+
+#[before_each]
+#[cfg(test)]
+mod async_opaque_guard {
+    use super::lock::{hold, is_held, released_by};
+    use super::*;
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    async fn before_each() -> impl Drop {
+        tokio::task::yield_now().await;
+        hold(&LOCK)
+    }
+
+    #[tokio::test]
+    async fn holds_the_guard_across_awaits() {
+        tokio::task::yield_now().await;
+        assert!(is_held(&LOCK));
+    }
+
+    #[no_hooks]
+    #[test]
+    fn drops_the_guard_when_the_test_finishes() {
+        assert_eq!(released_by(holds_the_guard_across_awaits), 1);
+    }
+}
+
+#[lifecycle]
+#[cfg(test)]
+mod lifecycle {
+    use super::lock::{hold, is_held, Held};
+    use std::sync::Mutex;
+    use test_env_helpers::TestInfo;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn before_each(info: &TestInfo) -> Held {
+        assert_eq!(info.name(), "holds_the_guard");
+        hold(&LOCK)
+    }
+    fn after_each() {}
+
+    #[test]
+    fn holds_the_guard() {
+        assert!(is_held(&LOCK));
+    }
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use super::lock::hold;
+    use std::sync::Mutex;
+
+    pub static LOCK: Mutex<()> = Mutex::new(());
+
+    fn before_each() -> impl Drop {
+        hold(&LOCK)
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::lock::{is_held, released_by};
+    use super::shared_hooks::LOCK;
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    fn holds_the_guard() {
+        assert!(is_held(&LOCK));
+    }
+
+    #[with_hooks(super::shared_hooks)]
+    #[tokio::test]
+    async fn holds_the_guard_across_awaits() {
+        tokio::task::yield_now().await;
+        assert!(is_held(&LOCK));
+    }
+
+    #[test]
+    fn drops_the_guards_when_the_tests_finish() {
+        assert_eq!(released_by(holds_the_guard), 1);
+        assert_eq!(released_by(holds_the_guard_across_awaits), 1);
+    }
+}
@@ -0,0 +1,55 @@
+use test_env_helpers::*;
+
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod inserted_hooks {
+    use super::*;
+
+    fn before_each(info: &TestInfo) {
+        if info.name().starts_with("fails_in_before_each") {
+            panic!("setup broke");
+        }
+    }
+    fn after_each(info: &TestInfo) {
+        if info.name() == "fails_in_after_each" {
+            panic!("teardown broke");
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "before_each hook failed for `hook_failure::inserted_hooks::fails_in_before_each` (hook at tests/hook_failure.rs:9): setup broke"
+    )]
+    fn fails_in_before_each() {}
+
+    #[test]
+    #[should_panic(
+        expected = "after_each hook failed for `hook_failure::inserted_hooks::fails_in_after_each` (hook at tests/hook_failure.rs:14): teardown broke"
+    )]
+    fn fails_in_after_each() {}
+
+    #[tokio::test]
+    #[should_panic(expected = "before_each hook failed for")]
+    async fn fails_in_before_each_async() {}
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    fn before_each() {
+        panic!("shared setup broke");
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    #[should_panic(
+        expected = "before_each hook failed for `hook_failure::other_tests::fails_in_shared_before_each` (hook at tests/hook_failure.rs:40): shared setup broke"
+    )]
+    fn fails_in_shared_before_each() {}
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_all]
+#[before_each]
+#[after_each]
+#[after_all]
+#[cfg(test)]
+mod user_items_named_like_generated_ones {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Stand-ins for `parking_lot::Once` and friends, which used to clash with the generated
+    // imports.
+    #[allow(dead_code)]
+    struct Once;
+    mod panic {}
+
+    static BEFORE_ALL: AtomicUsize = AtomicUsize::new(0);
+    static AFTER_ALL: AtomicUsize = AtomicUsize::new(0);
+    static REMAINING_TESTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_all() {
+        BEFORE_ALL.fetch_add(1, Ordering::SeqCst);
+    }
+    fn before_each() {
+        REMAINING_TESTS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_each() {
+        AFTER_ALL.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        assert_eq!(BEFORE_ALL.load(Ordering::SeqCst), 1);
+        assert_eq!(AFTER_ALL.load(Ordering::SeqCst), 2);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[test]
+    fn test_1() {
+        assert_eq!(BEFORE_ALL.load(Ordering::SeqCst), 1);
+    }
+    #[test]
+    fn test_2() {
+        assert!(REMAINING_TESTS.load(Ordering::SeqCst) > 0);
+    }
+}
+
+#[cfg(test)]
+mod locals_named_like_generated_ones {
+    use super::*;
+    use test_case::test_case;
+
+    #[retry(2)]
+    #[test_case(3)]
+    fn retried(driver: u8) {
+        assert_eq!(driver, 3);
+    }
+    #[repeat(2)]
+    #[test_case(3)]
+    fn repeated(result: u8) {
+        assert_eq!(result, 3);
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["hygiene::user_items_named_like_generated_ones"]);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+reported_tests! {
+    "TEST_ENV_HELPERS_JUNIT",
+    "setup <failed>",
+
+    #[test]
+    fn panicking() {
+        if reporting() {
+            panic!("broken & \"quoted\"\nsecond line");
+        }
+    }
+
+    #[skip]
+    #[test]
+    fn skipped() {}
+
+    #[skip]
+    mod skipped_mod {
+        #[test]
+        fn inner() {}
+    }
+}
+
+#[test]
+fn junit_env() {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    let dir = env::temp_dir().join(format!("test-env-helpers-junit-{}", process::id()));
+    let Some(mut rerun) = common::rerun_reported("TEST_ENV_HELPERS_JUNIT", &dir) else {
+        return;
+    };
+    let output = rerun.output().unwrap();
+    assert!(!output.status.success());
+    let xml = fs::read_to_string(dir.join("junit.xml")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"junit\" tests=\"7\" failures=\"1\" errors=\"1\" skipped=\"3\""));
+    assert!(xml.contains(
+        "<testsuite name=\"junit::reported\" tests=\"6\" failures=\"1\" errors=\"1\" skipped=\"2\""
+    ));
+    assert!(xml.contains(
+        "<testsuite name=\"junit::reported::skipped_mod\" tests=\"1\" failures=\"0\" errors=\"0\" skipped=\"1\""
+    ));
+    assert!(xml.contains("<testcase name=\"passing\" classname=\"junit::reported\" time=\""));
+    assert!(xml.contains("<testcase name=\"expected_panic\" classname=\"junit::reported\" time=\""));
+    assert_eq!(xml.matches("<failure").count(), 1);
+    assert!(xml.contains(
+        "<failure type=\"panicked\" message=\"broken &amp; &quot;quoted&quot;\">broken &amp; &quot;quoted&quot;\nsecond line</failure>"
+    ));
+    assert!(xml.contains(
+        "<error type=\"before_each\" message=\"setup &lt;failed&gt;\">setup &lt;failed&gt;</error>"
+    ));
+    assert!(xml.contains(
+        "<testcase name=\"skipped_by_tag\" classname=\"junit::reported\" time=\"0.000\">\n      <skipped/>"
+    ));
+    assert!(xml.contains("<testcase name=\"skipped\" classname=\"junit::reported\" time=\"0.000\">\n      <skipped/>"));
+    assert!(xml.contains("<testcase name=\"inner\" classname=\"junit::reported::skipped_mod\""));
+    assert!(xml.ends_with("  </testsuite>\n</testsuites>\n"));
+}
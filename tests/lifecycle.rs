@@ -0,0 +1,75 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[lifecycle]
+#[cfg(test)]
+mod lifecycle {
+    use super::*;
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+    }
+    static BEFORE_ALL_RUNS: AtomicUsize = AtomicUsize::new(0);
+    static AFTER_EACH_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+    fn events() -> Vec<&'static str> {
+        EVENTS.with(|events| events.borrow().clone())
+    }
+
+    fn after_all() {
+        // Every test that didn't panic has run `after_each` by the time `after_all` runs.
+        assert_eq!(AFTER_EACH_RUNS.load(Ordering::SeqCst), 3);
+        crate::common::after_all_ran(module_path!());
+    }
+    fn after_each() {
+        record("after_each");
+        assert_eq!(events(), ["before_each", "test", "after_each"]);
+        AFTER_EACH_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn before_each() {
+        EVENTS.with(|events| events.borrow_mut().clear());
+        record("before_each");
+    }
+    fn before_all() {
+        BEFORE_ALL_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_1() {
+        assert_eq!(BEFORE_ALL_RUNS.load(Ordering::SeqCst), 1);
+        assert_eq!(events(), ["before_each"]);
+        record("test");
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(events(), ["before_each"]);
+        record("test");
+    }
+    #[tokio::test]
+    async fn async_test() {
+        assert_eq!(events(), ["before_each"]);
+        tokio::task::yield_now().await;
+        record("test");
+    }
+    #[test]
+    #[should_panic]
+    fn failing_test() {
+        assert_eq!(0, 1);
+    }
+    #[no_hooks]
+    #[test]
+    fn without_hooks() {
+        assert!(events().is_empty());
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["lifecycle::lifecycle"]);
+}
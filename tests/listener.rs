@@ -0,0 +1,123 @@
+use test_env_helpers::*;
+
+#[cfg(test)]
+mod recorder {
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use test_env_helpers::*;
+
+    /// Records what happens to the tests in `listener::observed`.
+    pub struct Recorder(pub Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn record(&self, test: &TestInfo, event: String) {
+            if test.module_path() == "listener::observed" {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}: {}", test.name(), event));
+            }
+        }
+    }
+
+    impl TestListener for Recorder {
+        fn on_test_start(&self, test: &TestInfo) {
+            self.record(test, "start".to_string());
+        }
+        fn on_test_end(&self, test: &TestInfo, outcome: &TestOutcome, _: Duration) {
+            self.record(test, format!("end {:?}", outcome));
+        }
+        fn on_test_skipped(&self, test: &TestInfo) {
+            self.record(test, "skipped".to_string());
+        }
+        fn on_hook_start(&self, hook: &str, test: &TestInfo) {
+            self.record(test, format!("{} start", hook));
+        }
+        fn on_hook_end(&self, hook: &str, test: &TestInfo, outcome: &TestOutcome, _: Duration) {
+            self.record(test, format!("{} end {:?}", hook, outcome));
+        }
+        fn on_module_complete(&self, module_path: &str, summary: &ModuleSummary) {
+            if module_path == "listener::observed" {
+                self.0.lock().unwrap().push(format!(
+                    "complete: {} passed, {} failed",
+                    summary.passed(),
+                    summary.failed()
+                ));
+            }
+        }
+    }
+}
+
+#[listener]
+#[cfg(test)]
+static RECORDER: recorder::Recorder = recorder::Recorder(std::sync::Mutex::new(vec![]));
+
+#[before_each]
+#[after_all]
+#[cfg(test)]
+mod observed {
+    fn before_each() {}
+    fn after_all() {}
+
+    #[test]
+    fn passing() {}
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn panicking() {
+        panic!("boom");
+    }
+}
+
+#[cfg(test)]
+mod verify {
+    use super::*;
+
+    #[test]
+    fn calls_the_listener() {
+        let start = std::time::Instant::now();
+        let complete = || {
+            let events = RECORDER.0.lock().unwrap();
+            events.iter().any(|event| event.starts_with("complete"))
+        };
+        while !complete() && start.elapsed() < std::time::Duration::from_secs(10) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let events = RECORDER.0.lock().unwrap().clone();
+        // `after_all` runs as part of whichever test finishes last.
+        let of = |name: &str| -> Vec<&str> {
+            events
+                .iter()
+                .filter_map(|event| event.strip_prefix(name))
+                .filter(|event| !event.starts_with("after_all"))
+                .collect()
+        };
+        assert_eq!(
+            of("passing: "),
+            [
+                "start",
+                "before_each start",
+                "before_each end Passed",
+                "end Passed"
+            ]
+        );
+        assert_eq!(
+            of("panicking: "),
+            [
+                "start",
+                "before_each start",
+                "before_each end Passed",
+                "end Passed"
+            ]
+        );
+        let after_all: Vec<_> = events
+            .iter()
+            .filter_map(|event| event.split_once(": after_all "))
+            .map(|(_, event)| event)
+            .collect();
+        assert_eq!(after_all, ["start", "end Passed"]);
+        let position = |suffix: &str| events.iter().position(|event| event.ends_with(suffix));
+        let complete = position("complete: 2 passed, 0 failed").unwrap();
+        assert!(position(": after_all end Passed").unwrap() < complete);
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod captured {
+    use super::*;
+    use std::env;
+
+    fn before_each() {
+        tracing::info!("connecting to {}", "db");
+    }
+    fn after_each() {
+        captured_logs().assert_contains(Level::INFO, "connecting to db");
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn captures_log_records() {
+        log::warn!(target: "db", "retrying");
+        let logs = captured_logs();
+        logs.assert_contains(Level::WARN, "retrying");
+        logs.assert_not_contains(Level::ERROR, "retrying");
+        let record = &logs.records()[1];
+        assert_eq!(record.level(), Level::WARN);
+        assert_eq!(record.target(), "db");
+        assert_eq!(record.to_string(), " WARN db: retrying");
+    }
+
+    #[test]
+    fn captures_tracing_events() {
+        tracing::error!(attempt = 3, name = "users", "gave up");
+        tracing::debug!(done = true);
+        let logs = captured_logs();
+        logs.assert_contains(Level::ERROR, "gave up attempt=3 name=\"users\"");
+        logs.assert_contains(Level::DEBUG, "done=true");
+        assert_eq!(logs.records()[1].target(), "logs::captured");
+    }
+
+    #[cfg(not(feature = "log"))]
+    #[test]
+    fn only_captures_log_records_with_the_log_feature() {
+        log::warn!("retrying");
+        captured_logs().assert_not_contains(Level::WARN, "retrying");
+    }
+
+    #[tokio::test]
+    async fn captures_async_tests() {
+        tokio::task::yield_now().await;
+        tracing::info!("after the await");
+        captured_logs().assert_contains(Level::INFO, "after the await");
+    }
+
+    #[test]
+    fn keeps_tests_apart() {
+        tracing::warn!("retrying");
+        assert_eq!(captured_logs().records().len(), 2);
+        captured_logs().assert_not_contains(Level::DEBUG, "done=true");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "no WARN record containing `missing` was logged, the records were:\n   INFO logs::captured: connecting to db"
+    )]
+    fn assert_contains_lists_the_records() {
+        captured_logs().assert_contains(Level::WARN, "missing");
+    }
+
+    // Only fails when run by `prints_logs_of_failed_tests`.
+    #[test]
+    fn fails() {
+        tracing::warn!("retrying");
+        assert!(env::var("TEST_ENV_HELPERS_LOGS_FAIL").is_err());
+    }
+}
+
+#[test]
+fn not_captured_outside_of_tests() {
+    tracing::warn!("retrying");
+    assert!(captured_logs().records().is_empty());
+    // The subscriber capturing the events of a test is only the default of its thread while the
+    // test runs.
+    assert!(tracing::dispatcher::get_default(|dispatch| {
+        dispatch.is::<tracing::subscriber::NoSubscriber>()
+    }));
+}
+
+#[test]
+fn prints_logs_of_failed_tests() {
+    let Some(mut rerun) = common::rerun("TEST_ENV_HELPERS_LOGS_FAIL", "1") else {
+        return;
+    };
+    let output = rerun
+        .args(["captured::", "--test-threads=1"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "captured logs of `logs::captured::fails`:\n   INFO logs::captured: connecting to db\n   WARN logs::captured: retrying\n"
+    ));
+    assert_eq!(stdout.matches("captured logs of").count(), 1);
+}
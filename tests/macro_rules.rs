@@ -0,0 +1,99 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_all]
+#[before_each]
+#[after_each(tags = [counted])]
+#[after_all]
+#[cfg(test)]
+mod separate_hooks {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    static BEFORE_ALL_RUNS: AtomicUsize = AtomicUsize::new(0);
+    static AFTER_EACH_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn after_all() {
+        // The literal test and the two macro generated tests tagged `counted`.
+        assert_eq!(AFTER_EACH_RUNS.load(Ordering::SeqCst), 3);
+        crate::common::after_all_ran(module_path!());
+    }
+    fn after_each() {
+        AFTER_EACH_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn before_all() {
+        BEFORE_ALL_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    macro_rules! generated_test {
+        ($name:ident) => {
+            #[hooked]
+            #[tag(counted)]
+            #[test]
+            fn $name() {
+                assert_eq!(BEFORE_ALL_RUNS.load(Ordering::SeqCst), 1);
+                assert!(BEFORE_EACH_RAN.with(Cell::get));
+            }
+        };
+        ($name:ident, $value:expr) => {
+            #[hooked]
+            #[tokio::test]
+            async fn $name() {
+                assert!(BEFORE_EACH_RAN.with(Cell::get));
+                tokio::task::yield_now().await;
+                assert_eq!($value, 1);
+            }
+        };
+    }
+
+    generated_test!(generated_1);
+    generated_test!(generated_2);
+    generated_test!(generated_async, 1);
+
+    #[tag(counted)]
+    #[test]
+    fn literal_test() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+    }
+}
+
+#[lifecycle]
+#[cfg(test)]
+mod lifecycle {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static TESTS_RUN: AtomicUsize = AtomicUsize::new(0);
+
+    fn after_all() {
+        assert_eq!(TESTS_RUN.load(Ordering::SeqCst), 2);
+        crate::common::after_all_ran(module_path!());
+    }
+    fn after_each() {
+        TESTS_RUN.fetch_add(1, Ordering::SeqCst);
+    }
+
+    macro_rules! generated_tests {
+        ($($name:ident),*) => {
+            $(
+                #[hooked]
+                #[test]
+                fn $name() {}
+            )*
+        };
+    }
+
+    generated_tests!(generated_1, generated_2);
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["macro_rules::separate_hooks", "macro_rules::lifecycle"]);
+}
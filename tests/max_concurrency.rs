@@ -0,0 +1,83 @@
+use test_env_helpers::*;
+
+#[max_concurrency(2)]
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod max_concurrency {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use test_case::test_case;
+
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_each() {
+        assert!(RUNNING.fetch_add(1, Ordering::SeqCst) < 2);
+    }
+    fn after_each() {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_1() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    #[test]
+    fn test_2() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    #[test_case(1)]
+    #[test_case(2)]
+    fn test_case_macro(_: u8) {
+        thread::sleep(Duration::from_millis(5));
+    }
+    #[tokio::test]
+    async fn async_test_macro() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[cfg(test)]
+static SERVERS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(test)]
+fn start_server() {
+    use std::sync::atomic::Ordering;
+    assert!(SERVERS.fetch_add(1, Ordering::SeqCst) < 3);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    SERVERS.fetch_sub(1, Ordering::SeqCst);
+}
+
+#[max_concurrency(3, group = "servers")]
+#[cfg(test)]
+mod max_concurrency_group {
+    #[test]
+    fn test_1() {
+        super::start_server();
+    }
+    #[test]
+    fn test_2() {
+        super::start_server();
+    }
+    #[test]
+    fn test_3() {
+        super::start_server();
+    }
+}
+
+#[cfg(test)]
+mod max_concurrency_group_fn {
+    use super::*;
+
+    #[max_concurrency(3, group = "servers")]
+    #[test]
+    fn test_1() {
+        start_server();
+    }
+    #[max_concurrency(3, group = "servers")]
+    #[test]
+    fn test_2() {
+        start_server();
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_all]
+#[before_each]
+#[after_each]
+#[after_all]
+#[cfg(test)]
+mod no_hooks {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    static BEFORE_ALL_RUNS: AtomicUsize = AtomicUsize::new(0);
+    static AFTER_EACH_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_all() {
+        BEFORE_ALL_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn after_each() {
+        AFTER_EACH_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        assert_eq!(AFTER_EACH_RUNS.load(Ordering::SeqCst), 2);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[test]
+    fn hooked() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        assert_eq!(BEFORE_ALL_RUNS.load(Ordering::SeqCst), 1);
+    }
+    #[no_hooks]
+    #[test]
+    fn without_hooks() {
+        assert!(!BEFORE_EACH_RAN.with(Cell::get));
+    }
+    #[skip_before_each]
+    #[test]
+    fn without_before_each() {
+        assert!(!BEFORE_EACH_RAN.with(Cell::get));
+    }
+    #[skip_after_each]
+    #[test]
+    fn without_after_each() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["no_hooks::no_hooks"]);
+}
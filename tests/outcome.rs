@@ -0,0 +1,199 @@
+use test_env_helpers::*;
+
+#[cfg(test)]
+mod driver {
+    use std::cell::{Cell, RefCell};
+    use std::panic;
+    use test_env_helpers::TestOutcome;
+
+    thread_local! {
+        static DRIVEN: Cell<bool> = const { Cell::new(false) };
+        static OUTCOMES: RefCell<Vec<TestOutcome>> = const { RefCell::new(vec![]) };
+    }
+
+    /// Returns `true` if the test was called by [`outcomes_of`] rather than the test harness, so
+    /// it can fail in ways the harness wouldn't accept.
+    pub fn driven() -> bool {
+        DRIVEN.with(Cell::get)
+    }
+
+    pub fn record(outcome: &TestOutcome) {
+        OUTCOMES.with(|outcomes| outcomes.borrow_mut().push(outcome.clone()));
+    }
+
+    /// Runs `test` on this thread and returns the outcomes its `after_each` hook was passed.
+    pub fn outcomes_of<T>(test: fn() -> T) -> Vec<TestOutcome> {
+        OUTCOMES.with(|outcomes| outcomes.take());
+        DRIVEN.with(|driven| driven.set(true));
+        let _ = panic::catch_unwind(test);
+        DRIVEN.with(|driven| driven.set(false));
+        OUTCOMES.with(|outcomes| outcomes.take())
+    }
+}
+
+#[after_each]
+#[cfg(test)]
+mod inserted_hooks {
+    use super::driver::{driven, outcomes_of, record};
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        static FLAKY_FAILED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    fn after_each(info: &TestInfo, outcome: &TestOutcome) {
+        if info.name() != "reports_the_outcomes" {
+            record(outcome);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn expected_panic() {
+        panic!("boom");
+    }
+
+    #[test]
+    fn unexpected_panic() {
+        if driven() {
+            panic!("boom");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn wrong_panic_message() {
+        match driven() {
+            true => panic!("bang"),
+            false => panic!("boom"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_panic() {
+        if !driven() {
+            panic!();
+        }
+    }
+
+    #[retry(2)]
+    #[test]
+    fn flaky() -> Result<(), &'static str> {
+        if !FLAKY_FAILED.with(|failed| failed.replace(true)) {
+            return Err("first attempt");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_outcomes() {
+        assert_eq!(outcomes_of(expected_panic), [TestOutcome::Passed]);
+        assert_eq!(
+            outcomes_of(unexpected_panic),
+            [TestOutcome::Panicked("boom".to_string())]
+        );
+        assert_eq!(
+            outcomes_of(wrong_panic_message),
+            [TestOutcome::Panicked("bang".to_string())]
+        );
+        assert_eq!(
+            outcomes_of(missing_panic),
+            [TestOutcome::Failed(
+                "test did not panic as expected".to_string()
+            )]
+        );
+        FLAKY_FAILED.with(|failed| failed.set(false));
+        assert_eq!(
+            outcomes_of(flaky),
+            [
+                TestOutcome::Failed("\"first attempt\"".to_string()),
+                TestOutcome::Passed
+            ]
+        );
+    }
+}
+
+#[lifecycle]
+#[cfg(test)]
+mod lifecycle {
+    use super::driver::{driven, outcomes_of, record};
+    use super::*;
+
+    fn after_each(outcome: &TestOutcome) {
+        record(outcome);
+    }
+    fn before_each() {}
+
+    #[test]
+    #[should_panic]
+    fn expected_panic() {
+        panic!();
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn async_expected_panic() {
+        tokio::task::yield_now().await;
+        panic!();
+    }
+
+    #[tokio::test]
+    async fn async_unexpected_panic() {
+        tokio::task::yield_now().await;
+        if driven() {
+            panic!("boom");
+        }
+    }
+
+    #[test]
+    fn reports_the_outcomes() {
+        assert_eq!(outcomes_of(expected_panic), [TestOutcome::Passed]);
+        assert_eq!(outcomes_of(async_expected_panic), [TestOutcome::Passed]);
+        assert_eq!(
+            outcomes_of(async_unexpected_panic),
+            [TestOutcome::Panicked("boom".to_string())]
+        );
+    }
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use test_env_helpers::TestOutcome;
+
+    fn after_each(outcome: &TestOutcome) {
+        super::driver::record(outcome);
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::driver::{driven, outcomes_of};
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    #[should_panic]
+    fn expected_panic() {
+        panic!();
+    }
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    fn unexpected_panic() {
+        if driven() {
+            panic!("boom");
+        }
+    }
+
+    #[test]
+    fn reports_the_outcomes() {
+        assert_eq!(outcomes_of(expected_panic), [TestOutcome::Passed]);
+        assert_eq!(
+            outcomes_of(unexpected_panic),
+            [TestOutcome::Panicked("boom".to_string())]
+        );
+    }
+}
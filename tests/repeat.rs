@@ -0,0 +1,169 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[repeat(5)]
+#[before_each]
+#[after_all]
+#[cfg(test)]
+mod repeat {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use test_case::test_case;
+
+    static BEFORE_EACH: AtomicUsize = AtomicUsize::new(0);
+    static SEEDS: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+    fn before_each() {
+        BEFORE_EACH.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        // 5 iterations each for 3 tests and 2 cases, plus 2 for the overridden test
+        assert_eq!(BEFORE_EACH.load(Ordering::SeqCst), 27);
+        let seeds = SEEDS.lock().unwrap();
+        assert_eq!(seeds.iter().collect::<HashSet<_>>().len(), 5);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[test]
+    fn test_macro() {}
+    #[tokio::test]
+    async fn async_test_macro() {
+        tokio::task::yield_now().await;
+    }
+    #[test]
+    fn seeded_test() {
+        SEEDS.lock().unwrap().push(seed());
+        assert_eq!(seed(), seed());
+    }
+    #[test_case(1)]
+    #[test_case(2)]
+    fn test_case_repeat(n: usize) {
+        assert!(n > 0);
+    }
+    #[repeat(2)]
+    #[test]
+    fn overridden_iterations() {}
+}
+
+#[cfg(test)]
+mod repeat_fn {
+    use super::*;
+    use std::cell::Cell;
+    use std::panic;
+
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+        static DRIVEN: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Runs `test` on this thread, returning how many iterations it ran and its panic message.
+    fn iterations_of(test: fn()) -> (usize, Option<String>) {
+        ITERATIONS.with(|iterations| iterations.set(0));
+        DRIVEN.with(|driven| driven.set(true));
+        let result = panic::catch_unwind(test);
+        DRIVEN.with(|driven| driven.set(false));
+        let message = result.err().map(|err| match err.downcast::<String>() {
+            Ok(message) => *message,
+            Err(err) => err.downcast::<&str>().unwrap().to_string(),
+        });
+        (ITERATIONS.with(Cell::get), message)
+    }
+
+    fn next_iteration() -> usize {
+        ITERATIONS.with(|iterations| {
+            iterations.set(iterations.get() + 1);
+            iterations.get()
+        })
+    }
+
+    #[repeat(10)]
+    fn fails_on_third_iteration() {
+        let iteration = next_iteration();
+        if iteration == 3 {
+            panic!("failed on iteration {}", iteration);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_failing_iteration() {
+        assert_eq!(
+            iterations_of(fails_on_third_iteration),
+            (3, Some("failed on iteration 3".to_string()))
+        );
+    }
+
+    #[repeat(3)]
+    #[test]
+    #[should_panic(expected = "expected")]
+    fn expected_panic() {
+        panic!("expected on iteration {}", next_iteration());
+    }
+
+    #[repeat(3)]
+    #[test]
+    #[should_panic]
+    fn missing_panic() {
+        // Only when called by `repeats_expected_panics`, so the harness still sees it panic.
+        if next_iteration() == 2 && DRIVEN.with(Cell::get) {
+            return;
+        }
+        panic!("expected");
+    }
+
+    #[test]
+    fn repeats_expected_panics() {
+        assert_eq!(
+            iterations_of(expected_panic),
+            (3, Some("expected on iteration 3".to_string()))
+        );
+        // An iteration that doesn't panic fails the test, which the harness reports as not having
+        // panicked.
+        assert_eq!(iterations_of(missing_panic), (2, None));
+    }
+    #[repeat(3)]
+    #[test]
+    fn returns_result() -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Run by `reports_failing_iterations` with `REPEAT_FAILING` set, which makes the tests fail.
+#[before_each]
+#[cfg(test)]
+mod failing {
+    use super::*;
+
+    fn before_each() {}
+
+    #[repeat(3)]
+    #[test]
+    fn repeated() {
+        assert!(std::env::var_os("REPEAT_FAILING").is_none());
+    }
+
+    #[test]
+    fn hooked() {
+        assert!(std::env::var_os("REPEAT_FAILING").is_none());
+    }
+}
+
+#[test]
+fn reports_failing_iterations() {
+    let Some(mut rerun) = common::rerun("REPEAT_FAILING", "1") else {
+        return;
+    };
+    let output = rerun.args(["failing::", "--nocapture"]).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("`repeat::failing::repeated` failed on iteration 1/3 with a panic"));
+    // The hooked test is only run once, so it doesn't report an iteration.
+    assert!(!stderr.contains("`repeat::failing::hooked` failed on iteration"));
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["repeat::repeat"]);
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[retry(3)]
+#[before_each]
+#[after_all]
+#[cfg(test)]
+mod retry {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use test_case::test_case;
+
+    static BEFORE_EACH: AtomicUsize = AtomicUsize::new(0);
+    static SYNC_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    static ASYNC_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_each() {
+        BEFORE_EACH.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        // 3 + 2 + 1 attempts, 1 attempt for each of the 2 cases, 1 attempt for the expected panic
+        // and 1 attempt for the override
+        assert_eq!(BEFORE_EACH.load(Ordering::SeqCst), 10);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[test]
+    fn passes_on_third_attempt() {
+        if SYNC_ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+            panic!("flaky");
+        }
+    }
+    #[tokio::test]
+    async fn async_passes_on_second_attempt() {
+        tokio::task::yield_now().await;
+        if ASYNC_ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 1 {
+            panic!("flaky");
+        }
+    }
+    #[test]
+    fn passes_on_first_attempt() {}
+    #[test_case(1)]
+    #[test_case(2)]
+    fn test_case_retry(n: usize) {
+        assert!(n > 0);
+    }
+    #[test]
+    #[should_panic]
+    fn expected_panic() {
+        panic!("not retried");
+    }
+    #[retry(1)]
+    #[test]
+    fn overridden_attempts() {}
+}
+
+#[cfg(test)]
+mod retry_fn {
+    use super::*;
+    use std::cell::Cell;
+    use std::panic;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static ERR_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static ATTEMPTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Runs `test` on this thread, returning how many attempts it took and its panic message.
+    fn attempts_of(test: fn()) -> (usize, Option<String>) {
+        ATTEMPTS.with(|attempts| attempts.set(0));
+        let result = panic::catch_unwind(test);
+        let message = result.err().map(|err| match err.downcast::<String>() {
+            Ok(message) => *message,
+            Err(err) => err.downcast::<&str>().unwrap().to_string(),
+        });
+        (ATTEMPTS.with(Cell::get), message)
+    }
+
+    fn next_attempt() -> usize {
+        ATTEMPTS.with(|attempts| {
+            attempts.set(attempts.get() + 1);
+            attempts.get()
+        })
+    }
+
+    #[retry(2)]
+    #[test]
+    fn returns_err_then_ok() -> Result<(), String> {
+        if ERR_ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err("flaky".to_string());
+        }
+        Ok(())
+    }
+    #[retry(2)]
+    fn fails_every_attempt() {
+        panic!("broken on attempt {}", next_attempt());
+    }
+    #[retry(3)]
+    #[test]
+    #[should_panic(expected = "expected")]
+    fn panics_as_expected_on_second_attempt() {
+        match next_attempt() {
+            1 => panic!("flaky"),
+            attempt => panic!("expected on attempt {}", attempt),
+        }
+    }
+    #[test]
+    fn retries_until_the_expected_panic() {
+        assert_eq!(
+            attempts_of(fails_every_attempt),
+            (2, Some("broken on attempt 2".to_string()))
+        );
+        assert_eq!(
+            attempts_of(panics_as_expected_on_second_attempt),
+            (2, Some("expected on attempt 2".to_string()))
+        );
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["retry::retry"]);
+}
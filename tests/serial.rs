@@ -0,0 +1,81 @@
+use test_env_helpers::*;
+
+#[serial]
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod serial {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use test_case::test_case;
+
+    static RUNNING: AtomicBool = AtomicBool::new(false);
+
+    fn before_each() {
+        assert!(!RUNNING.swap(true, Ordering::SeqCst));
+    }
+    fn after_each() {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_macro() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    #[test_case(1)]
+    #[test_case(2)]
+    fn async_test_macro_2(_: u8) {
+        thread::sleep(Duration::from_millis(5));
+    }
+    #[tokio::test]
+    async fn async_test_macro() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    #[test]
+    #[should_panic]
+    fn failing_test() {
+        RUNNING.store(false, Ordering::SeqCst);
+        assert_eq!(0, 1, "the lock is released anyway");
+    }
+}
+
+#[cfg(test)]
+static DB_RUNNING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(test)]
+fn use_db() {
+    use std::sync::atomic::Ordering;
+    assert!(!DB_RUNNING.swap(true, Ordering::SeqCst));
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    DB_RUNNING.store(false, Ordering::SeqCst);
+}
+
+#[serial(group = "db")]
+#[cfg(test)]
+mod serial_group {
+    #[test]
+    fn test_1() {
+        super::use_db();
+    }
+    #[test]
+    fn test_2() {
+        super::use_db();
+    }
+}
+
+#[cfg(test)]
+mod serial_group_fn {
+    use super::*;
+
+    #[serial(group = "db")]
+    #[test]
+    fn test_1() {
+        use_db();
+    }
+    #[serial(group = "db")]
+    #[test]
+    fn test_2() {
+        use_db();
+    }
+}
@@ -1,7 +1,6 @@
 #[cfg(test)]
 mod skip {
     use test_env_helpers::*;
-    use tokio;
 
     #[skip]
     mod inner_skip {
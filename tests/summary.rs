@@ -0,0 +1,118 @@
+use test_env_helpers::*;
+
+/// Waits for `after_all` to store the summary of a mod. Checking it in `after_all` itself could go
+/// unnoticed, since the last test to finish may be one that is expected to panic.
+#[cfg(test)]
+fn wait_for(summary: &std::sync::Mutex<Option<ModuleSummary>>) -> ModuleSummary {
+    let start = std::time::Instant::now();
+    while start.elapsed() < std::time::Duration::from_secs(10) {
+        if let Some(summary) = summary.lock().unwrap().clone() {
+            return summary;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("`after_all` didn't run");
+}
+
+#[after_all]
+#[cfg(test)]
+mod inserted_hooks {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    static SUMMARY: Mutex<Option<ModuleSummary>> = Mutex::new(None);
+
+    fn after_all(summary: &ModuleSummary) {
+        *SUMMARY.lock().unwrap() = Some(summary.clone());
+    }
+
+    #[test]
+    fn passing() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    #[test]
+    #[should_panic]
+    fn panicking() {
+        panic!();
+    }
+
+    macro_rules! generated_test {
+        ($name:ident) => {
+            #[hooked]
+            #[test]
+            fn $name() {}
+        };
+    }
+    generated_test!(generated);
+
+    // Neither is run, so `after_all` doesn't wait for them.
+    #[skip]
+    #[test]
+    fn skipped() {}
+
+    #[ignore]
+    #[test]
+    fn ignored() {}
+
+    #[no_hooks]
+    #[test]
+    fn summary() {
+        let summary = wait_for(&SUMMARY);
+        assert_eq!(summary.executed(), 3);
+        // A test that panics as expected has passed.
+        assert_eq!(summary.passed(), 3);
+        assert_eq!(summary.failed(), 0);
+        assert_eq!(summary.skipped(), 1);
+        assert!(summary.duration() >= Duration::from_millis(50));
+        assert!(summary
+            .to_string()
+            .starts_with("3 passed, 0 failed, 1 skipped in "));
+    }
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use std::sync::Mutex;
+    use test_env_helpers::ModuleSummary;
+
+    pub static SUMMARY: Mutex<Option<ModuleSummary>> = Mutex::new(None);
+
+    fn after_all(summary: &ModuleSummary) {
+        *SUMMARY.lock().unwrap() = Some(summary.clone());
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    fn passing() {}
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    #[should_panic]
+    fn panicking() {
+        panic!();
+    }
+
+    #[with_hooks(super::shared_hooks)]
+    #[skip]
+    #[test]
+    fn skipped() {}
+
+    #[with_hooks(super::shared_hooks)]
+    #[ignore]
+    #[test]
+    fn ignored() {}
+
+    #[test]
+    fn summary() {
+        let summary = wait_for(&super::shared_hooks::SUMMARY);
+        assert_eq!((summary.passed(), summary.failed()), (2, 0));
+    }
+}
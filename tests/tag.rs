@@ -0,0 +1,104 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_each(tags = [db])]
+#[after_each(tags = [db])]
+#[tag(fast)]
+#[cfg(test)]
+mod tag {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static DB: Cell<bool> = const { Cell::new(false) };
+    }
+    static DB_TESTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_each() {
+        DB.with(|db| db.set(true));
+    }
+    fn after_each() {
+        DB_TESTS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[tag(db)]
+    #[test]
+    fn tagged() {
+        assert!(DB.with(Cell::get));
+    }
+    #[tag(db, slow)]
+    #[tokio::test]
+    async fn multiple_tags() {
+        assert!(DB.with(Cell::get));
+    }
+    #[test]
+    fn untagged() {
+        assert!(!DB.with(Cell::get));
+    }
+}
+
+// Run by `test_tags_env` with `TEST_TAGS=db,!slow`. The tests that would fail when `TEST_TAGS` is
+// set must be skipped.
+#[before_all]
+#[after_all]
+#[cfg(test)]
+mod selected {
+    use super::*;
+    use std::env;
+
+    fn before_all() {}
+    fn after_all() {
+        eprintln!("after_all ran");
+    }
+
+    #[tag(db)]
+    #[test]
+    fn db() {}
+    #[tag(db, slow)]
+    #[test]
+    fn db_slow() {
+        assert!(env::var("TEST_TAGS").is_err());
+    }
+    #[tag(other)]
+    #[test]
+    fn other() {
+        assert!(env::var("TEST_TAGS").is_err());
+    }
+    #[test]
+    fn untagged() {
+        assert!(env::var("TEST_TAGS").is_err());
+    }
+}
+
+#[cfg(test)]
+mod selected_fn {
+    use super::*;
+    use std::env;
+
+    #[tag(slow)]
+    #[test]
+    fn returns_result() -> Result<(), String> {
+        match env::var("TEST_TAGS") {
+            Ok(_) => Err("should be skipped".to_string()),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_tags_env() {
+    let Some(mut rerun) = common::rerun("TEST_TAGS", "db,!slow") else {
+        return;
+    };
+    let output = rerun
+        .args(["selected", "--test-threads=1", "--nocapture"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("skipping `tag::selected::db_slow`"));
+    assert!(stderr.contains("skipping `tag::selected::untagged`"));
+    assert!(stderr.contains("skipping `tag::selected_fn::returns_result`"));
+    assert!(stderr.contains("after_all ran"));
+}
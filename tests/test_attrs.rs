@@ -0,0 +1,198 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[before_each]
+#[after_all]
+#[cfg(test)]
+mod rstest_cases {
+    use rstest::rstest;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    static T: AtomicUsize = AtomicUsize::new(0);
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn after_all() {
+        assert_eq!(T.load(Ordering::SeqCst), 9);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[rstest]
+    fn single() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+    #[rstest]
+    #[case(1)]
+    #[case::two(2)]
+    fn cases(#[case] n: usize) {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        assert!(n > 0);
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    fn cases_and_values(#[case] n: usize, #[values(1, 2, 3)] m: usize) {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        assert!(n > 0 && m > 0);
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[before_each]
+#[after_all]
+#[cfg(test)]
+mod async_cases {
+    use rstest::rstest;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use test_case::test_case;
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    static T: AtomicUsize = AtomicUsize::new(0);
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn after_all() {
+        assert_eq!(T.load(Ordering::SeqCst), 4);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[test_case(1)]
+    #[test_case(2)]
+    #[tokio::test]
+    async fn test_cases(n: usize) {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        assert!(n > 0);
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[tokio::test]
+    async fn rstest_cases(#[case] n: usize) {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        assert!(n > 0);
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[before_each(test_attrs = [it])]
+#[after_all(test_attrs = [it])]
+#[cfg(test)]
+mod custom_test_attr {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::test as it;
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    static T: AtomicUsize = AtomicUsize::new(0);
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn after_all() {
+        assert_eq!(T.load(Ordering::SeqCst), 2);
+        crate::common::after_all_ran(module_path!());
+    }
+
+    #[it]
+    async fn custom() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+    #[test]
+    fn builtin() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+        T.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[retry(2, test_attrs = [it])]
+#[serial(test_attrs = [it])]
+#[cfg(test)]
+mod custom_test_attr_retry {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::test as it;
+
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    #[it]
+    async fn passes_on_second_attempt() {
+        if ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("flaky");
+        }
+    }
+}
+
+#[repeat(3, test_attrs = [it])]
+#[cfg(test)]
+mod custom_test_attr_repeat {
+    use std::cell::Cell;
+    use tokio::test as it;
+
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[it]
+    async fn repeated() {
+        ITERATIONS.with(|iterations| iterations.set(iterations.get() + 1));
+    }
+
+    #[test]
+    fn repeats_the_custom_tests() {
+        ITERATIONS.with(|iterations| iterations.set(0));
+        repeated();
+        assert_eq!(ITERATIONS.with(Cell::get), 3);
+    }
+}
+
+#[tag(db, test_attrs = [it])]
+#[before_each(tags = [db], test_attrs = [it])]
+#[cfg(test)]
+mod custom_test_attr_tag {
+    use std::cell::Cell;
+    use tokio::test as it;
+
+    thread_local! {
+        static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+
+    #[it]
+    async fn tagged() {
+        assert!(BEFORE_EACH_RAN.with(Cell::get));
+    }
+}
+
+#[skip(test_attrs = [it])]
+#[cfg(test)]
+mod custom_test_attr_skip {
+    use tokio::test as it;
+
+    #[it]
+    async fn skipped() {
+        panic!("skipped");
+    }
+}
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&[
+        "test_attrs::rstest_cases",
+        "test_attrs::async_cases",
+        "test_attrs::custom_test_attr",
+    ]);
+}
@@ -0,0 +1,80 @@
+use test_env_helpers::*;
+
+#[before_each]
+#[after_each]
+#[cfg(test)]
+mod each_hooks {
+    use super::*;
+    use std::cell::RefCell;
+    use test_case::test_case;
+
+    thread_local! {
+        static SEEN_BY_BEFORE_EACH: RefCell<Option<TestInfo>> = const { RefCell::new(None) };
+    }
+
+    fn before_each(info: &TestInfo) {
+        SEEN_BY_BEFORE_EACH.with(|seen| *seen.borrow_mut() = Some(info.clone()));
+    }
+    fn after_each(test: &test_env_helpers::TestInfo) {
+        assert_eq!(Some(test.clone()), current_test());
+    }
+
+    fn seen_by_before_each() -> TestInfo {
+        SEEN_BY_BEFORE_EACH.with(|seen| seen.borrow().clone().unwrap())
+    }
+
+    #[tag(db, api)]
+    #[test]
+    fn named_test() {
+        let info = seen_by_before_each();
+        assert_eq!(info.name(), "named_test");
+        assert_eq!(info.module_path(), "test_info::each_hooks");
+        assert_eq!(info.full_name(), "test_info::each_hooks::named_test");
+        assert_eq!(info.tags(), ["api", "db"]);
+        assert!(info.args().is_empty());
+    }
+
+    struct NotDebug;
+
+    #[test_case(1, "one")]
+    #[test_case(2, "two")]
+    fn case_test(number: u8, name: &str) {
+        let info = seen_by_before_each();
+        assert_eq!(info.args(), [number.to_string(), format!("{:?}", name)]);
+    }
+
+    #[test_case(NotDebug)]
+    fn not_debug_arg(_value: NotDebug) {
+        assert_eq!(seen_by_before_each().args(), ["_"]);
+    }
+
+    #[tokio::test]
+    async fn async_test() {
+        tokio::task::yield_now().await;
+        assert_eq!(current_test().unwrap().name(), "async_test");
+    }
+}
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use test_env_helpers::TestInfo;
+
+    fn before_each(info: &TestInfo) {
+        assert_eq!(info.name(), "with_shared_hooks");
+    }
+}
+
+#[cfg(test)]
+mod other_tests {
+    use super::*;
+
+    #[with_hooks(super::shared_hooks)]
+    #[test]
+    fn with_shared_hooks() {}
+
+    #[test]
+    fn without_hooks() {
+        assert_eq!(current_test(), None);
+    }
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use test_env_helpers::*;
+
+// Run by `timings_env` with `TEST_ENV_HELPERS_TIMINGS` and `TEST_ENV_HELPERS_SLOW_MS` set.
+#[before_all]
+#[before_each]
+#[cfg(test)]
+mod timed {
+    use std::thread;
+    use std::time::Duration;
+
+    fn before_all() {
+        thread::sleep(Duration::from_millis(20));
+    }
+    fn before_each() {}
+
+    #[test]
+    fn slow() {
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    #[test]
+    fn fast() {}
+}
+
+#[test]
+fn timings_env() {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    let dir = env::temp_dir().join(format!("test-env-helpers-timings-{}", process::id()));
+    let Some(mut rerun) = common::rerun("TEST_ENV_HELPERS_TIMINGS", &dir) else {
+        return;
+    };
+    let output = rerun
+        .args(["timed", "--test-threads=1"])
+        .env("TEST_ENV_HELPERS_SLOW_MS", "100")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    let report = fs::read_to_string(dir.join("timings.txt")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // The number of seconds right after `prefix` in `text`, e.g. `0.203` in `took 0.203s`.
+    let seconds_after = |text: &str, prefix: &str| -> f64 {
+        let start = text
+            .find(prefix)
+            .unwrap_or_else(|| panic!("no `{}` in:\n{}", prefix, text));
+        let seconds = text[start + prefix.len()..].split('s').next().unwrap();
+        seconds.parse().unwrap()
+    };
+    assert!(seconds_after(&stderr, "slow test `timings::timed::slow` took ") >= 0.2);
+    assert!(stderr.contains("over the 100ms of TEST_ENV_HELPERS_SLOW_MS"));
+    assert!(!stderr.contains("timings::timed::fast"));
+
+    assert!(report.starts_with("Timings of the tests in `timings`, slowest first\n"));
+    assert!(seconds_after(&report, "\ntimings::timed: 2 tests took ") >= 0.2);
+    let hooks = report.find("  hooks:\n    before_all ").unwrap();
+    let before_each = report.find("\n    before_each ").unwrap();
+    let tests = report.find("  tests:\n").unwrap();
+    assert!(hooks < before_each && before_each < tests);
+    assert!(report.contains(", 2x, slowest "));
+    let slow = report.find("s slow (").unwrap();
+    let fast = report.find("s fast (").unwrap();
+    assert!(tests < slow && slow < fast);
+}
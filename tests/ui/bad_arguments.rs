@@ -0,0 +1,23 @@
+use test_env_helpers::*;
+
+#[serial(2)]
+mod serial_with_limit {}
+
+#[max_concurrency]
+mod max_concurrency_without_limit {}
+
+#[max_concurrency(2, groups = "db")]
+mod unknown_argument {}
+
+#[repeat]
+mod repeat_without_count {}
+
+#[tag("db")]
+mod string_tag {}
+
+#[before_each(tag = [db])]
+mod unknown_hook_argument {
+    fn before_each() {}
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use test_env_helpers::*;
+
+#[after_each]
+mod my_tests {
+    fn after_each() {}
+    fn after_each() {}
+    #[test]
+    fn test_1() {}
+}
+
+fn main() {}
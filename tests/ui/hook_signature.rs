@@ -0,0 +1,26 @@
+use test_env_helpers::*;
+
+#[before_each]
+mod generic {
+    fn before_each<T>() {}
+    #[test]
+    fn test_1() {}
+}
+
+#[after_each]
+mod arguments_and_return {
+    fn after_each(value: u8) -> u8 {
+        value
+    }
+    #[test]
+    fn test_1() {}
+}
+
+#[before_all]
+mod async_all {
+    async fn before_all() {}
+    #[test]
+    fn test_1() {}
+}
+
+fn main() {}
@@ -0,0 +1,25 @@
+use test_env_helpers::*;
+
+#[lifecycle]
+#[before_each]
+mod combined {
+    fn before_each() {}
+    #[test]
+    fn test_1() {}
+}
+
+#[before_each]
+#[lifecycle]
+mod combined_after {
+    fn before_each() {}
+    #[test]
+    fn test_1() {}
+}
+
+#[lifecycle]
+mod no_hooks {
+    #[test]
+    fn test_1() {}
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use test_env_helpers::*;
+
+#[before_each]
+mod my_tests {
+    #[test]
+    fn test_1() {}
+}
+
+fn main() {}
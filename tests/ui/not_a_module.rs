@@ -0,0 +1,12 @@
+use test_env_helpers::*;
+
+#[before_all]
+struct NotAModule;
+
+#[retry(3)]
+const NOT_A_TEST: u8 = 0;
+
+#[no_hooks]
+mod not_a_test {}
+
+fn main() {}
@@ -0,0 +1,39 @@
+#![deny(deprecated)]
+use test_env_helpers::*;
+
+#[after_all]
+mod no_tests {
+    fn after_all() {}
+    fn not_a_test() {}
+}
+
+#[before_each]
+mod missing_attribute {
+    fn before_each() {}
+    fn after_each() {}
+    #[test]
+    fn test_1() {}
+}
+
+#[before_each(tags = [db])]
+mod no_tagged_tests {
+    fn before_each() {}
+    #[test]
+    fn test_1() {}
+}
+
+mod skipped {
+    use super::*;
+
+    #[skip]
+    fn helper() {}
+
+    #[skip]
+    struct Helper;
+
+    #[skip]
+    #[test]
+    fn broken_test() {}
+}
+
+fn main() {}
@@ -0,0 +1,41 @@
+mod common;
+
+use test_env_helpers::*;
+
+#[hooks]
+#[cfg(test)]
+mod shared_hooks {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        pub static BEFORE_EACH_RAN: Cell<bool> = const { Cell::new(false) };
+    }
+    pub static BEFORE_ALL_RUNS: AtomicUsize = AtomicUsize::new(0);
+    pub static AFTER_EACH_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn before_all() {
+        BEFORE_ALL_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn before_each() {
+        BEFORE_EACH_RAN.with(|ran| ran.set(true));
+    }
+    fn after_each() {
+        AFTER_EACH_RUNS.fetch_add(1, Ordering::SeqCst);
+    }
+    fn after_all() {
+        // `failing_test` panics before `after_each` and `without_after_each` opts out of it.
+        assert_eq!(AFTER_EACH_RUNS.load(Ordering::SeqCst), 4);
+        crate::common::after_all_ran(module_path!());
+    }
+}
+
+// A file-backed `mod tests;` whose tests opt into the hooks one by one.
+#[cfg(test)]
+#[path = "with_hooks/tests.rs"]
+mod tests;
+
+#[test]
+fn after_all_runs() {
+    common::assert_after_all_ran(&["with_hooks::shared_hooks"]);
+}
@@ -0,0 +1,47 @@
+use super::shared_hooks::{BEFORE_ALL_RUNS, BEFORE_EACH_RAN};
+use std::cell::Cell;
+use std::sync::atomic::Ordering;
+use test_case::test_case;
+use test_env_helpers::*;
+
+#[with_hooks(super::shared_hooks)]
+#[test]
+fn test_1() {
+    assert_eq!(BEFORE_ALL_RUNS.load(Ordering::SeqCst), 1);
+    assert!(BEFORE_EACH_RAN.with(Cell::get));
+}
+
+#[with_hooks(crate::shared_hooks)]
+#[test_case(1)]
+#[test_case(2)]
+fn test_cases(n: u8) {
+    assert!(n > 0);
+    assert!(BEFORE_EACH_RAN.with(Cell::get));
+}
+
+#[with_hooks(super::shared_hooks)]
+#[tokio::test]
+async fn async_test() {
+    tokio::task::yield_now().await;
+    assert!(BEFORE_EACH_RAN.with(Cell::get));
+}
+
+#[with_hooks(super::shared_hooks)]
+#[test]
+#[should_panic]
+fn failing_test() {
+    assert_eq!(0, 1);
+}
+
+#[with_hooks(super::shared_hooks)]
+#[skip_after_each]
+#[skip_before_each]
+#[test]
+fn without_after_each() {
+    assert!(!BEFORE_EACH_RAN.with(Cell::get));
+}
+
+#[test]
+fn without_hooks() {
+    assert!(!BEFORE_EACH_RAN.with(Cell::get));
+}